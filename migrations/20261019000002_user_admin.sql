@@ -0,0 +1,3 @@
+-- Admins may edit any recipe and reassign recipe owners
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT false;
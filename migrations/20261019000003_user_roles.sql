@@ -0,0 +1,11 @@
+-- Replace the admin flag with viewer, editor and admin roles. New accounts are viewers, existing ones keep the
+-- editing they could already do
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS role VARCHAR NOT NULL DEFAULT 'viewer'
+        CHECK (role IN ('viewer', 'editor', 'admin'));
+
+UPDATE users SET role = 'admin' WHERE is_admin;
+UPDATE users SET role = 'editor' WHERE NOT is_admin;
+
+ALTER TABLE users
+    DROP COLUMN IF EXISTS is_admin;
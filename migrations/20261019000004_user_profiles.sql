@@ -0,0 +1,5 @@
+-- Public user profiles
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS display_name VARCHAR,
+    ADD COLUMN IF NOT EXISTS bio TEXT,
+    ADD COLUMN IF NOT EXISTS avatar_uri VARCHAR;
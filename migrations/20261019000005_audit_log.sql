@@ -0,0 +1,28 @@
+-- Append-only record of every write
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor_name VARCHAR NOT NULL,
+    -- Null when acting through an auth file token
+    actor_user_id BIGINT,
+    resource VARCHAR NOT NULL,
+    resource_id VARCHAR NOT NULL,
+    action VARCHAR NOT NULL,
+    -- Changed fields as { "field": { "before": ..., "after": ... } }
+    changes JSONB NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_resource ON audit_log (resource, resource_id);
+CREATE INDEX IF NOT EXISTS audit_log_actor_name ON audit_log (actor_name);
+CREATE INDEX IF NOT EXISTS audit_log_actor_user_id ON audit_log (actor_user_id);
+
+CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log;
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
@@ -0,0 +1,40 @@
+-- Soft deleted recipes stay in the trash until restored or deleted permanently
+ALTER TABLE recipes
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS recipes_deleted_at ON recipes (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Hide trashed recipes from every read
+CREATE OR REPLACE VIEW recipe_overviews AS
+    SELECT recipes.id AS recipe_id,
+        recipes.name AS recipe_name,
+        recipes.brief_description,
+        recipes.image_uri,
+        recipes.user_id,
+        users.name AS user_name
+    FROM recipes
+    JOIN users ON users.id = recipes.user_id
+    WHERE recipes.deleted_at IS NULL;
+
+CREATE OR REPLACE VIEW recipe_details AS
+    SELECT recipes.id AS recipe_id,
+        recipes.name AS recipe_name,
+        recipes.brief_description,
+        recipes.method,
+        recipes.image_uri,
+        recipes.user_id,
+        users.name AS user_name
+    FROM recipes
+    JOIN users ON users.id = recipes.user_id
+    WHERE recipes.deleted_at IS NULL;
+
+CREATE OR REPLACE VIEW recipe_ingredients_list AS
+    SELECT recipe_ingredients.recipe_id,
+        recipe_ingredients.ingredient_id,
+        recipes.name AS recipe_name,
+        ingredients.name AS ingredient_name,
+        recipe_ingredients.amount
+    FROM recipe_ingredients
+    JOIN recipes ON recipes.id = recipe_ingredients.recipe_id
+    JOIN ingredients ON ingredients.id = recipe_ingredients.ingredient_id
+    WHERE recipes.deleted_at IS NULL;
@@ -0,0 +1,3 @@
+-- Bumped on every edit, returned as the recipe's ETag
+ALTER TABLE recipes
+    ADD COLUMN IF NOT EXISTS version BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,32 @@
+-- Every version of every recipe, numbered by the recipe's version
+CREATE TABLE IF NOT EXISTS recipe_revisions (
+    id BIGSERIAL PRIMARY KEY,
+    recipe_id BIGINT NOT NULL REFERENCES recipes (id) ON DELETE CASCADE,
+    revision BIGINT NOT NULL,
+    -- Fields and ingredient list as { recipe_name, brief_description, method, image_uri, user_id, ingredients }
+    content JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor_name VARCHAR NOT NULL,
+    actor_user_id BIGINT,
+    UNIQUE (recipe_id, revision)
+);
+
+-- Existing recipes start their history at their current version
+INSERT INTO recipe_revisions (recipe_id, revision, content, actor_name)
+    SELECT recipes.id,
+        recipes.version,
+        jsonb_build_object(
+            'recipe_name', recipes.name,
+            'brief_description', recipes.brief_description,
+            'method', recipes.method,
+            'image_uri', recipes.image_uri,
+            'user_id', recipes.user_id,
+            'ingredients', COALESCE((
+                SELECT jsonb_agg(jsonb_build_object('ingredient_id', recipe_ingredients.ingredient_id, 'amount', recipe_ingredients.amount) ORDER BY recipe_ingredients.id)
+                FROM recipe_ingredients
+                WHERE recipe_ingredients.recipe_id = recipes.id
+            ), '[]'::jsonb)
+        ),
+        'migration'
+    FROM recipes
+    ON CONFLICT (recipe_id, revision) DO NOTHING;
@@ -0,0 +1,6 @@
+-- Views of a recipe's page, for sorting by popularity
+ALTER TABLE recipes
+    ADD COLUMN IF NOT EXISTS view_count BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS recipes_name_id ON recipes (name, id);
+CREATE INDEX IF NOT EXISTS recipes_view_count_id ON recipes (view_count DESC, id DESC);
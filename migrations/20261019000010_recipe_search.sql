@@ -0,0 +1,62 @@
+-- Full-text search over a recipe's name, description, method and ingredient names
+ALTER TABLE recipes
+    ADD COLUMN IF NOT EXISTS search_vector TSVECTOR;
+
+CREATE OR REPLACE FUNCTION recipe_search_vector(recipe recipes) RETURNS TSVECTOR AS $$
+    SELECT setweight(to_tsvector('english', recipe.name), 'A')
+        || setweight(to_tsvector('english', recipe.brief_description), 'B')
+        || setweight(to_tsvector('english', COALESCE((
+            SELECT string_agg(ingredients.name, ' ')
+            FROM recipe_ingredients
+            JOIN ingredients ON ingredients.id = recipe_ingredients.ingredient_id
+            WHERE recipe_ingredients.recipe_id = recipe.id
+        ), '')), 'B')
+        || setweight(to_tsvector('english', COALESCE(recipe.method, '')), 'C');
+$$ LANGUAGE sql STABLE;
+
+CREATE OR REPLACE FUNCTION recipes_update_search_vector() RETURNS trigger AS $$
+BEGIN
+    NEW.search_vector := recipe_search_vector(NEW);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS recipes_search_vector ON recipes;
+CREATE TRIGGER recipes_search_vector
+    BEFORE INSERT OR UPDATE OF name, brief_description, method ON recipes
+    FOR EACH ROW EXECUTE FUNCTION recipes_update_search_vector();
+
+-- Ingredient changes re-index the recipes they touch
+CREATE OR REPLACE FUNCTION recipe_ingredients_update_search_vector() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        UPDATE recipes SET search_vector = recipe_search_vector(recipes) WHERE id = OLD.recipe_id;
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        UPDATE recipes SET search_vector = recipe_search_vector(recipes) WHERE id = NEW.recipe_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS recipe_ingredients_search_vector ON recipe_ingredients;
+CREATE TRIGGER recipe_ingredients_search_vector
+    AFTER INSERT OR UPDATE OR DELETE ON recipe_ingredients
+    FOR EACH ROW EXECUTE FUNCTION recipe_ingredients_update_search_vector();
+
+CREATE OR REPLACE FUNCTION ingredients_update_search_vector() RETURNS trigger AS $$
+BEGIN
+    UPDATE recipes SET search_vector = recipe_search_vector(recipes)
+        WHERE id IN (SELECT recipe_id FROM recipe_ingredients WHERE ingredient_id = NEW.id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS ingredients_search_vector ON ingredients;
+CREATE TRIGGER ingredients_search_vector
+    AFTER UPDATE OF name ON ingredients
+    FOR EACH ROW EXECUTE FUNCTION ingredients_update_search_vector();
+
+UPDATE recipes SET search_vector = recipe_search_vector(recipes);
+
+CREATE INDEX IF NOT EXISTS recipes_search_vector ON recipes USING GIN (search_vector);
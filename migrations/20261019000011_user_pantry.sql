@@ -0,0 +1,12 @@
+-- Ingredients each user has at home
+CREATE TABLE IF NOT EXISTS user_pantry_items (
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    ingredient_id BIGINT NOT NULL REFERENCES ingredients (id) ON DELETE CASCADE,
+    -- Free text, e.g. "500g" or "half a jar"
+    quantity VARCHAR,
+    expires_on DATE,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, ingredient_id)
+);
+
+CREATE INDEX IF NOT EXISTS user_pantry_items_expires_on ON user_pantry_items (user_id, expires_on) WHERE expires_on IS NOT NULL;
@@ -0,0 +1,24 @@
+-- Signals for ranking recipes by score
+ALTER TABLE recipes
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Existing recipes are dated by their first revision
+UPDATE recipes
+SET created_at = recipe_revisions.created_at
+FROM recipe_revisions
+WHERE recipe_revisions.recipe_id = recipes.id AND recipe_revisions.revision = 1;
+
+CREATE TABLE IF NOT EXISTS recipe_ratings (
+    recipe_id BIGINT NOT NULL REFERENCES recipes (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    rating SMALLINT NOT NULL CHECK (rating BETWEEN 1 AND 5),
+    rated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (recipe_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS recipe_favorites (
+    recipe_id BIGINT NOT NULL REFERENCES recipes (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (recipe_id, user_id)
+);
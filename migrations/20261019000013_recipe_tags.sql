@@ -0,0 +1,34 @@
+-- Tags group recipes, e.g. "vegetarian", "pasta" or "weeknight"
+CREATE TABLE IF NOT EXISTS tags (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS tags_name_lower ON tags (lower(name));
+
+CREATE TABLE IF NOT EXISTS recipe_tags (
+    recipe_id BIGINT NOT NULL REFERENCES recipes (id) ON DELETE CASCADE,
+    tag_id BIGINT NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (recipe_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS recipe_tags_tag_id ON recipe_tags (tag_id);
+
+-- Overviews list each recipe's tag names
+CREATE OR REPLACE VIEW recipe_overviews AS
+    SELECT recipes.id AS recipe_id,
+        recipes.name AS recipe_name,
+        recipes.brief_description,
+        recipes.image_uri,
+        recipes.user_id,
+        users.name AS user_name,
+        ARRAY(
+            SELECT tags.name::TEXT
+            FROM recipe_tags
+            JOIN tags ON tags.id = recipe_tags.tag_id
+            WHERE recipe_tags.recipe_id = recipes.id
+            ORDER BY tags.name
+        ) AS tags
+    FROM recipes
+    JOIN users ON users.id = recipes.user_id
+    WHERE recipes.deleted_at IS NULL;
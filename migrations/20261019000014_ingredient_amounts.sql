@@ -0,0 +1,20 @@
+-- Amounts as a quantity in a unit plus a note, next to the free text amount, which now holds their display form.
+-- Existing amounts are parsed into these columns by running the server with --migrate-amounts
+ALTER TABLE recipe_ingredients
+    ADD COLUMN IF NOT EXISTS quantity DOUBLE PRECISION CHECK (quantity > 0),
+    ADD COLUMN IF NOT EXISTS unit VARCHAR,
+    ADD COLUMN IF NOT EXISTS note VARCHAR;
+
+CREATE OR REPLACE VIEW recipe_ingredients_list AS
+    SELECT recipe_ingredients.recipe_id,
+        recipe_ingredients.ingredient_id,
+        recipes.name AS recipe_name,
+        ingredients.name AS ingredient_name,
+        recipe_ingredients.amount,
+        recipe_ingredients.quantity,
+        recipe_ingredients.unit,
+        recipe_ingredients.note
+    FROM recipe_ingredients
+    JOIN recipes ON recipes.id = recipe_ingredients.recipe_id
+    JOIN ingredients ON ingredients.id = recipe_ingredients.ingredient_id
+    WHERE recipes.deleted_at IS NULL;
@@ -0,0 +1,2 @@
+-- How many people a recipe serves, which ingredient amounts can be scaled from. Unknown for existing recipes
+ALTER TABLE recipes ADD COLUMN IF NOT EXISTS servings INTEGER CHECK (servings > 0);
@@ -0,0 +1,7 @@
+-- The ingredient each merged ingredient was folded into, so revisions written before a merge can still be restored
+CREATE TABLE IF NOT EXISTS ingredient_merges (
+    merged_id BIGINT PRIMARY KEY,
+    into_id BIGINT NOT NULL REFERENCES ingredients (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS ingredient_merges_into_id ON ingredient_merges (into_id);
@@ -0,0 +1,9 @@
+-- Views counted by the hour they were made in, so listings can rank recipes by their views as of a fixed time
+CREATE TABLE IF NOT EXISTS recipe_view_hours (
+    recipe_id BIGINT NOT NULL REFERENCES recipes (id) ON DELETE CASCADE,
+    hour TIMESTAMPTZ NOT NULL,
+    views BIGINT NOT NULL,
+    PRIMARY KEY (recipe_id, hour)
+);
+
+CREATE INDEX IF NOT EXISTS recipe_view_hours_hour ON recipe_view_hours (hour);
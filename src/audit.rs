@@ -0,0 +1,107 @@
+mod get_audit_log;
+
+use futures::executor::block_on;
+use http::{Method, Request, Response};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{PgExecutor, PgPool};
+use crate::authorization::{Authorization, Identity};
+use crate::http::responses::{bad_request_response, method_not_allowed_response};
+use crate::recipe::chunk_url;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Create => "create",
+            Action::Update => "update",
+            Action::Delete => "delete",
+            Action::Restore => "restore",
+        }
+    }
+}
+
+/// A write to record, with the state of the resource before and after it
+pub struct AuditEntry<'a> {
+    pub actor: &'a Identity,
+    pub resource: &'static str,
+    pub resource_id: String,
+    pub action: Action,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+pub fn can_handle_request(request: &Request<Vec<u8>>) -> bool {
+    request.uri().path() == "/audit"
+}
+
+pub fn handle_request(request: Request<Vec<u8>>, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
+    match *request.method() {
+        Method::GET => handle_get_request(&request, db_pool, auth_handler),
+        _ => method_not_allowed_response()
+    }
+}
+
+fn handle_get_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    if chunk_url(request.uri()).len() != 1 {
+        return bad_request_response()
+    }
+
+    if let Err(err) = authorization.authenticate_route(request) {
+        return err.response();
+    }
+
+    block_on(get_audit_log::get_audit_log(request, db_pool))
+}
+
+/// Append an entry to the audit log
+pub async fn record<'e>(executor: impl PgExecutor<'e>, entry: AuditEntry<'_>) -> anyhow::Result<()> {
+    let changes = diff(entry.before.as_ref(), entry.after.as_ref());
+    sqlx::query("INSERT INTO audit_log
+            (actor_name, actor_user_id, resource, resource_id, action, changes)
+            VALUES ($1, $2, $3, $4, $5, $6);")
+        .bind(&entry.actor.name)
+        .bind(entry.actor.user_id)
+        .bind(entry.resource)
+        .bind(&entry.resource_id)
+        .bind(entry.action.as_str())
+        .bind(changes)
+        .execute(executor).await?;
+    Ok(())
+}
+
+/// Record an entry, logging rather than failing if it can't be written - the write it describes has already happened
+pub async fn record_or_log<'e>(executor: impl PgExecutor<'e>, entry: AuditEntry<'_>) {
+    let description = format!("{} {} {} by {}", entry.action.as_str(), entry.resource, entry.resource_id, entry.actor);
+    if let Err(err) = record(executor, entry).await {
+        log::error!("Failed to write audit log entry for {} - {}", description, err);
+    }
+}
+
+/// Fields that differ between two snapshots, as `{ "field": { "before": ..., "after": ... } }`
+pub(crate) fn diff(before: Option<&Value>, after: Option<&Value>) -> Value {
+    let empty = Map::new();
+    let before_fields = before.and_then(Value::as_object).unwrap_or(&empty);
+    let after_fields = after.and_then(Value::as_object).unwrap_or(&empty);
+
+    let mut changes = Map::new();
+    for key in before_fields.keys().chain(after_fields.keys().filter(|key| !before_fields.contains_key(*key))) {
+        let before = before_fields.get(key).unwrap_or(&Value::Null);
+        let after = after_fields.get(key).unwrap_or(&Value::Null);
+        if before != after {
+            let mut change = Map::new();
+            change.insert("before".to_string(), before.clone());
+            change.insert("after".to_string(), after.clone());
+            changes.insert(key.clone(), Value::Object(change));
+        }
+    }
+    Value::Object(changes)
+}
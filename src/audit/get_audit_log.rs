@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use http::{Request, Response};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use crate::http::QueryParams;
+use crate::http::responses::{bad_request_response_with_message, internal_server_error_response, json_ok};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct AuditLogItem {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub actor_name: String,
+    pub actor_user_id: Option<i64>,
+    pub resource: String,
+    pub resource_id: String,
+    pub action: String,
+    pub changes: Value,
+}
+
+/// Newest entries first, filtered by `resource`, `resource_id`, `actor` and `actor_user_id`.
+/// Page backwards through older entries with `before`, the lowest id already seen
+pub async fn get_audit_log(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
+    let params = QueryParams::from_uri(request.uri());
+    let (actor_user_id, before, limit) = match (params.parse::<i64>("actor_user_id"), params.parse::<i64>("before"), params.parse::<i64>("limit")) {
+        (Ok(actor_user_id), Ok(before), Ok(limit)) => (actor_user_id, before, limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)),
+        (Err(message), _, _) | (_, Err(message), _) | (_, _, Err(message)) => return bad_request_response_with_message(&message),
+    };
+
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new("SELECT * FROM audit_log WHERE TRUE");
+    if let Some(resource) = params.get("resource") {
+        query.push(" AND resource = ").push_bind(resource);
+    }
+    if let Some(resource_id) = params.get("resource_id") {
+        query.push(" AND resource_id = ").push_bind(resource_id);
+    }
+    if let Some(actor) = params.get("actor") {
+        query.push(" AND actor_name = ").push_bind(actor);
+    }
+    if let Some(actor_user_id) = actor_user_id {
+        query.push(" AND actor_user_id = ").push_bind(actor_user_id);
+    }
+    if let Some(before) = before {
+        query.push(" AND id < ").push_bind(before);
+    }
+    query.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+
+    let entries: Vec<AuditLogItem> = match query.build_query_as().fetch_all(db_pool).await {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::error!("Error handling get audit log request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    match serde_json::to_string(&entries) {
+        Ok(json) => json_ok(json),
+        Err(err) => {
+            log::error!("Error handling get audit log request: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
@@ -1,35 +1,238 @@
-use std::fs;
-use std::path::PathBuf;
+mod auth_error;
+mod credentials;
+mod permissions;
+mod role;
+mod scope;
+mod session;
+mod token_store;
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use std::sync::{Arc, RwLock};
+use futures::executor::block_on;
 use http::Request;
+use sqlx::PgPool;
+use credentials::Credentials;
+use token_store::TokenStore;
+use crate::Config;
+use crate::http::ClientAddress;
+use crate::rate_limit::{Budget, RateLimiter};
+
+pub use auth_error::AuthError;
+pub use role::Role;
+pub use scope::Scope;
+pub use session::Session;
 
 pub struct Authorization {
-    auth_file: PathBuf,
+    tokens: RwLock<TokenStore>,
+    session_lifetime: RwLock<Duration>,
+    db_pool: PgPool,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+/// The caller a request was authorized for - either a named token from the auth file or a logged in user
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub name: String,
+    pub user_id: Option<i64>,
+    pub scopes: Vec<Scope>,
+}
+
+impl Identity {
+    pub fn is_admin(&self) -> bool {
+        self.scopes.contains(&Scope::Admin)
+    }
+
+    /// Whether the caller may modify something owned by `owner_id`
+    pub fn may_modify(&self, owner_id: i64) -> bool {
+        self.is_admin() || self.user_id == Some(owner_id)
+    }
+
+    fn rate_limit_key(&self) -> String {
+        match self.user_id {
+            Some(user_id) => format!("user:{}", user_id),
+            None => format!("token:{}", self.name),
+        }
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.user_id {
+            Some(user_id) => f.write_fmt(format_args!("user '{}' ({})", self.name, user_id)),
+            None => f.write_fmt(format_args!("token '{}'", self.name)),
+        }
+    }
 }
 
 impl Authorization {
-    pub fn new(auth_file: PathBuf) -> Self {
-        Self { auth_file }
+    pub fn new(config: &Config, db_pool: PgPool, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            tokens: RwLock::new(TokenStore::new(config.auth_file.clone())),
+            session_lifetime: RwLock::new(config.session_lifetime),
+            db_pool,
+            rate_limiter,
+        }
+    }
+
+    /// Pick up auth settings from a reloaded config
+    pub fn apply_config(&self, config: &Config) {
+        let mut tokens = self.tokens.write().expect("token store lock poisoned");
+        if tokens.auth_file() != config.auth_file {
+            *tokens = TokenStore::new(config.auth_file.clone());
+        }
+        *self.session_lifetime.write().expect("session lifetime lock poisoned") = config.session_lifetime;
+    }
+
+    /// Authenticate a request for the scope its route requires in `permissions`
+    pub fn authenticate_route(&self, request: &Request<Vec<u8>>) -> Result<Identity, AuthError> {
+        match permissions::required_scope(request) {
+            Some(scope) => self.authenticate_request(request, scope),
+            None => {
+                log::error!("No permission declared for {} {}", request.method(), request.uri().path());
+                Err(AuthError::Internal)
+            }
+        }
     }
 
-    pub fn authenticate_request(&self, request: &Request<Vec<u8>>) -> anyhow::Result<()> {
+    pub fn authenticate_request(&self, request: &Request<Vec<u8>>, scope: Scope) -> Result<Identity, AuthError> {
         log::info!("Authorizing request");
-        let expected_token = fs::read_to_string(&self.auth_file)?.trim().to_string();
-        log::debug!("Loaded auth token");
-        let token_header = request.headers().get("Authorization");
-        let token_header = match token_header {
-            Some(header) => header,
+        self.check_failed_auth_budget(request)?;
+        self.reload_tokens_if_stale();
+
+        let identity = match self.authenticate_credentials(request, scope) {
+            Ok(identity) => identity,
+            Err(err) => {
+                if matches!(err, AuthError::InvalidRequest(_) | AuthError::InvalidToken) {
+                    self.record_failed_auth(request);
+                }
+                return Err(err);
+            }
+        };
+
+        if let Err(retry_after) = self.rate_limiter.try_acquire(Budget::Write, &identity.rate_limit_key()) {
+            return Err(AuthError::RateLimited(retry_after));
+        }
+
+        log::info!("Request authorized for {} with scope {}", identity, scope);
+        Ok(identity)
+    }
+
+    /// Refuse clients that have used up their failed authentication budget
+    pub fn check_failed_auth_budget(&self, request: &Request<Vec<u8>>) -> Result<(), AuthError> {
+        match ClientAddress::of(request) {
+            Some(client) => self.rate_limiter.check(Budget::FailedAuth, &client.to_string()).map_err(AuthError::RateLimited),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_failed_auth(&self, request: &Request<Vec<u8>>) {
+        if let Some(client) = ClientAddress::of(request) {
+            log::info!("Failed authentication from {}", client);
+            let _ = self.rate_limiter.try_acquire(Budget::FailedAuth, &client.to_string());
+        }
+    }
+
+    fn authenticate_credentials(&self, request: &Request<Vec<u8>>, scope: Scope) -> Result<Identity, AuthError> {
+        let credentials = Self::credentials(request)?;
+        let identity = match &credentials {
+            Credentials::Bearer(token) => self.authenticate_bearer(token, scope)?,
+            Credentials::Basic { name, token } => {
+                let tokens = self.tokens.read().expect("token store lock poisoned");
+                match tokens.find(token).filter(|found| &found.name == name) {
+                    Some(token) => Self::check_token_scope(token, scope)?,
+                    None => {
+                        log::info!("Bad authorization header");
+                        return Err(AuthError::InvalidToken);
+                    }
+                }
+            }
+        };
+        Ok(identity)
+    }
+
+    /// Start a session for a user that has proven their identity
+    pub async fn start_session(&self, user_id: i64) -> anyhow::Result<Session> {
+        let lifetime = *self.session_lifetime.read().expect("session lifetime lock poisoned");
+        session::create_session(&self.db_pool, user_id, lifetime).await
+    }
+
+    /// End the session whose token authorizes `request`
+    pub async fn end_session(&self, request: &Request<Vec<u8>>) -> Result<(), AuthError> {
+        let token = match Self::credentials(request)? {
+            Credentials::Bearer(token) => token,
+            Credentials::Basic { .. } => return Err(AuthError::InvalidRequest("Sessions use bearer tokens")),
+        };
+
+        match session::delete_session(&self.db_pool, &token).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AuthError::InvalidToken),
+            Err(err) => {
+                log::error!("Failed to end session - {}", err);
+                Err(AuthError::Internal)
+            }
+        }
+    }
+
+    fn credentials(request: &Request<Vec<u8>>) -> Result<Credentials, AuthError> {
+        match request.headers().get(http::header::AUTHORIZATION) {
+            Some(header) => Credentials::try_from(header),
             None => {
                 log::info!("Missing authorization header");
-                anyhow::bail!("Missing Authorization header")
+                Err(AuthError::MissingCredentials)
             },
-        };
+        }
+    }
 
-        if expected_token != token_header.to_str()? {
-            log::info!("Bad authorization header");
-            anyhow::bail!("Invalid token");
+    fn authenticate_bearer(&self, token: &str, scope: Scope) -> Result<Identity, AuthError> {
+        if let Some(found) = self.tokens.read().expect("token store lock poisoned").find(token) {
+            return Self::check_token_scope(found, scope);
         }
 
-        log::info!("Request authorized");
-        Ok(())
+        match block_on(session::find_session_user(&self.db_pool, token)) {
+            Ok(Some(user)) => {
+                let role = match Role::from_str(&user.role) {
+                    Ok(role) => role,
+                    Err(err) => {
+                        log::error!("User '{}' has an invalid role - {}", user.user_name, err);
+                        return Err(AuthError::Internal);
+                    }
+                };
+                if !role.scopes().contains(&scope) {
+                    log::info!("User '{}' with role {} is missing scope {}", user.user_name, role, scope);
+                    return Err(AuthError::InsufficientScope(scope));
+                }
+                Ok(Identity { name: user.user_name, user_id: Some(user.user_id), scopes: role.scopes().to_vec() })
+            },
+            Ok(None) => {
+                log::info!("Bad authorization header");
+                Err(AuthError::InvalidToken)
+            },
+            Err(err) => {
+                log::error!("Failed to look up session - {}", err);
+                Err(AuthError::Internal)
+            }
+        }
     }
-}
\ No newline at end of file
+
+    fn check_token_scope(token: &token_store::AuthToken, scope: Scope) -> Result<Identity, AuthError> {
+        let scopes = token.granted_scopes();
+        if !scopes.contains(&scope) {
+            log::info!("Token '{}' is missing scope {}", token.name, scope);
+            return Err(AuthError::InsufficientScope(scope));
+        }
+        Ok(Identity { name: token.name.clone(), user_id: None, scopes })
+    }
+
+    fn reload_tokens_if_stale(&self) {
+        if !self.tokens.read().expect("token store lock poisoned").is_stale() {
+            return;
+        }
+
+        let mut tokens = self.tokens.write().expect("token store lock poisoned");
+        if let Err(err) = tokens.reload() {
+            log::error!("Failed to load auth tokens from {} - {}", tokens.auth_file().display(), err);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use http::Response;
+use crate::authorization::Scope;
+use crate::http::responses;
+
+/// Realm advertised in `WWW-Authenticate` challenges
+const REALM: &str = "alacarte";
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// No credentials were presented
+    MissingCredentials,
+    /// The `Authorization` header could not be understood
+    InvalidRequest(&'static str),
+    /// The credentials did not match any known token
+    InvalidToken,
+    /// The credentials are valid, but do not grant the required scope
+    InsufficientScope(Scope),
+    /// Too many failed attempts - retry after the given time
+    RateLimited(Duration),
+    /// The credentials could not be checked
+    Internal,
+}
+
+impl AuthError {
+    /// RFC 6750 error response, carrying a `WWW-Authenticate` challenge
+    pub fn response(&self) -> Response<Vec<u8>> {
+        let description = self.to_string();
+        match self {
+            AuthError::MissingCredentials => {
+                responses::unauthorized_response_with_challenge(&format!("Bearer realm=\"{}\"", REALM), &description)
+            },
+            AuthError::InvalidRequest(_) => {
+                let challenge = challenge("invalid_request", &description, None);
+                responses::bad_request_response_with_challenge(&challenge, &description)
+            },
+            AuthError::InvalidToken => {
+                let challenge = challenge("invalid_token", &description, None);
+                responses::unauthorized_response_with_challenge(&challenge, &description)
+            },
+            AuthError::InsufficientScope(scope) => {
+                let challenge = challenge("insufficient_scope", &description, Some(*scope));
+                responses::forbidden_response_with_challenge(&challenge, &description)
+            },
+            AuthError::RateLimited(retry_after) => responses::too_many_requests_response(*retry_after),
+            AuthError::Internal => responses::internal_server_error_response(),
+        }
+    }
+}
+
+fn challenge(error: &str, description: &str, scope: Option<Scope>) -> String {
+    let mut challenge = format!("Bearer realm=\"{}\", error=\"{}\", error_description=\"{}\"", REALM, error, description);
+    if let Some(scope) = scope {
+        challenge += &format!(", scope=\"{}\"", scope);
+    }
+    challenge
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingCredentials => f.write_str("Missing Authorization header"),
+            AuthError::InvalidRequest(reason) => f.write_str(reason),
+            AuthError::InvalidToken => f.write_str("Invalid token"),
+            AuthError::InsufficientScope(scope) => f.write_fmt(format_args!("Token is missing scope {}", scope)),
+            AuthError::RateLimited(retry_after) => f.write_fmt(format_args!("Too many attempts, retry after {:?}", retry_after)),
+            AuthError::Internal => f.write_str("Failed to check credentials"),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
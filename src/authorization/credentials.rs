@@ -0,0 +1,43 @@
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use crate::authorization::AuthError;
+
+/// Credentials presented in an `Authorization` header
+pub enum Credentials {
+    /// RFC 6750 bearer token
+    Bearer(String),
+    /// RFC 7617 basic credentials, with the token name as the user
+    Basic { name: String, token: String },
+}
+
+impl TryFrom<&http::HeaderValue> for Credentials {
+    type Error = AuthError;
+
+    fn try_from(value: &http::HeaderValue) -> Result<Self, Self::Error> {
+        let value = value.to_str().map_err(|_| AuthError::InvalidRequest("Authorization header is not valid ASCII"))?;
+        let (scheme, parameters) = value.trim().split_once(' ').unwrap_or((value, ""));
+        let parameters = parameters.trim();
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            if !is_token68(parameters) {
+                return Err(AuthError::InvalidRequest("Malformed bearer token"));
+            }
+            return Ok(Credentials::Bearer(parameters.to_string()));
+        }
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = BASE64_STANDARD.decode(parameters).map_err(|_| AuthError::InvalidRequest("Malformed basic credentials"))?;
+            let decoded = String::from_utf8(decoded).map_err(|_| AuthError::InvalidRequest("Malformed basic credentials"))?;
+            let (name, token) = decoded.split_once(':').ok_or(AuthError::InvalidRequest("Malformed basic credentials"))?;
+            return Ok(Credentials::Basic { name: name.to_string(), token: token.to_string() });
+        }
+
+        Err(AuthError::InvalidRequest("Unsupported authorization scheme"))
+    }
+}
+
+/// `token68` from RFC 7235 - the syntax of a bearer token
+fn is_token68(value: &str) -> bool {
+    let token = value.trim_end_matches('=');
+    !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || "-._~+/".contains(c))
+}
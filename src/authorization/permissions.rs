@@ -0,0 +1,52 @@
+use http::{Method, Request};
+use crate::authorization::Scope;
+
+/// The scope a caller needs to use a route
+struct RoutePermission {
+    method: Method,
+    /// Path with `*` matching any single segment
+    path: &'static str,
+    scope: Scope,
+}
+
+/// Every route requiring authorization. Routes missing from here are refused by `Authorization::authenticate_route`
+const ROUTE_PERMISSIONS: &[RoutePermission] = &[
+    RoutePermission { method: Method::POST, path: "/recipe", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::PUT, path: "/recipe/*", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::PATCH, path: "/recipe/*", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::DELETE, path: "/recipe/*", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::GET, path: "/recipe/trash", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::POST, path: "/recipe/*/restore", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::POST, path: "/recipe/*/revision/*/restore", scope: Scope::RecipeWrite },
+    RoutePermission { method: Method::PUT, path: "/recipe/*/rating", scope: Scope::RecipeRate },
+    RoutePermission { method: Method::DELETE, path: "/recipe/*/rating", scope: Scope::RecipeRate },
+    RoutePermission { method: Method::PUT, path: "/recipe/*/favorite", scope: Scope::RecipeRate },
+    RoutePermission { method: Method::DELETE, path: "/recipe/*/favorite", scope: Scope::RecipeRate },
+    RoutePermission { method: Method::POST, path: "/ingredient", scope: Scope::IngredientWrite },
+    RoutePermission { method: Method::POST, path: "/ingredient/*/merge", scope: Scope::IngredientMerge },
+    RoutePermission { method: Method::POST, path: "/image", scope: Scope::ImageWrite },
+    RoutePermission { method: Method::DELETE, path: "/image/*", scope: Scope::ImageDelete },
+    RoutePermission { method: Method::PUT, path: "/user/*", scope: Scope::ProfileWrite },
+    RoutePermission { method: Method::PUT, path: "/user/*/role", scope: Scope::RoleAssign },
+    RoutePermission { method: Method::GET, path: "/user/*/pantry", scope: Scope::Pantry },
+    RoutePermission { method: Method::GET, path: "/user/*/pantry/*", scope: Scope::Pantry },
+    RoutePermission { method: Method::POST, path: "/user/*/pantry", scope: Scope::Pantry },
+    RoutePermission { method: Method::PUT, path: "/user/*/pantry/*", scope: Scope::Pantry },
+    RoutePermission { method: Method::DELETE, path: "/user/*/pantry/*", scope: Scope::Pantry },
+    RoutePermission { method: Method::POST, path: "/tag", scope: Scope::TagWrite },
+    RoutePermission { method: Method::PUT, path: "/tag/*", scope: Scope::TagWrite },
+    RoutePermission { method: Method::DELETE, path: "/tag/*", scope: Scope::TagWrite },
+    RoutePermission { method: Method::GET, path: "/audit", scope: Scope::AuditRead },
+];
+
+pub fn required_scope(request: &Request<Vec<u8>>) -> Option<Scope> {
+    ROUTE_PERMISSIONS.iter()
+        .find(|permission| permission.method == request.method() && path_matches(permission.path, request.uri().path()))
+        .map(|permission| permission.scope)
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<&str>>();
+    let path = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<&str>>();
+    pattern.len() == path.len() && pattern.iter().zip(path).all(|(pattern, segment)| *pattern == "*" || *pattern == segment)
+}
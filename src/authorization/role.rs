@@ -0,0 +1,68 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use crate::authorization::Scope;
+
+/// A named set of scopes, held by users and optionally by auth tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "&'static str")]
+pub enum Role {
+    /// Read only, bar their own profile, pantry, ratings and favorites
+    Viewer,
+    /// Create and edit recipes, ingredients, images and tags
+    Editor,
+    /// Everything, including editing other users' recipes
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Editor, Role::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn scopes(&self) -> &'static [Scope] {
+        match self {
+            Role::Viewer => &[Scope::ProfileWrite, Scope::Pantry, Scope::RecipeRate],
+            Role::Editor => &[Scope::RecipeWrite, Scope::IngredientWrite, Scope::ImageWrite, Scope::ProfileWrite, Scope::Pantry, Scope::TagWrite, Scope::RecipeRate],
+            Role::Admin => &Scope::ALL,
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match Role::ALL.into_iter().find(|role| role.as_str() == value) {
+            Some(role) => Ok(role),
+            None => bail!("Unknown role '{}'", value),
+        }
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Role::from_str(&value)
+    }
+}
+
+impl From<Role> for &'static str {
+    fn from(value: Role) -> Self {
+        value.as_str()
+    }
+}
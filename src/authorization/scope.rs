@@ -0,0 +1,84 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use anyhow::bail;
+use serde::Deserialize;
+
+/// An operation a caller may be allowed to perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Scope {
+    RecipeWrite,
+    IngredientWrite,
+    ImageWrite,
+    ProfileWrite,
+    IngredientMerge,
+    ImageDelete,
+    RoleAssign,
+    AuditRead,
+    /// Read and edit one's own pantry
+    Pantry,
+    TagWrite,
+    /// Rate and favorite recipes as oneself
+    RecipeRate,
+    /// Edit anything, regardless of who owns it
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 12] = [
+        Scope::RecipeWrite,
+        Scope::IngredientWrite,
+        Scope::ImageWrite,
+        Scope::ProfileWrite,
+        Scope::IngredientMerge,
+        Scope::ImageDelete,
+        Scope::RoleAssign,
+        Scope::AuditRead,
+        Scope::Pantry,
+        Scope::TagWrite,
+        Scope::RecipeRate,
+        Scope::Admin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::RecipeWrite => "recipe:write",
+            Scope::IngredientWrite => "ingredient:write",
+            Scope::ImageWrite => "image:write",
+            Scope::ProfileWrite => "profile:write",
+            Scope::IngredientMerge => "ingredient:merge",
+            Scope::ImageDelete => "image:delete",
+            Scope::RoleAssign => "role:assign",
+            Scope::AuditRead => "audit:read",
+            Scope::Pantry => "pantry",
+            Scope::TagWrite => "tag:write",
+            Scope::RecipeRate => "recipe:rate",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match Scope::ALL.into_iter().find(|scope| scope.as_str() == value) {
+            Some(scope) => Ok(scope),
+            None => bail!("Unknown scope '{}'", value),
+        }
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Scope::from_str(&value)
+    }
+}
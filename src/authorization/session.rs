@@ -0,0 +1,74 @@
+use std::time::Duration;
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+const SESSION_TOKEN_BYTES: usize = 32;
+
+/// A newly started session. The token is only ever available here - only its hash is stored
+#[derive(Debug, Serialize)]
+pub struct Session {
+    pub user_id: i64,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct SessionUser {
+    pub user_id: i64,
+    pub user_name: String,
+    pub role: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct InsertedSession {
+    expires_at: DateTime<Utc>,
+}
+
+pub async fn create_session(db_pool: &PgPool, user_id: i64, lifetime: Duration) -> anyhow::Result<Session> {
+    let mut token_bytes = [0u8; SESSION_TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut token_bytes);
+    let token = BASE64_URL_SAFE_NO_PAD.encode(token_bytes);
+
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("DELETE FROM user_sessions WHERE user_id = $1 AND expires_at <= now();")
+        .bind(user_id)
+        .execute(&mut *tx).await?;
+    let inserted: InsertedSession = sqlx::query_as("INSERT INTO user_sessions
+            (user_id, token_hash, expires_at)
+            VALUES ($1, $2, now() + make_interval(secs => $3))
+            RETURNING expires_at;")
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .bind(lifetime.as_secs_f64())
+        .fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+
+    Ok(Session { user_id, token, expires_at: inserted.expires_at })
+}
+
+pub async fn find_session_user(db_pool: &PgPool, token: &str) -> anyhow::Result<Option<SessionUser>> {
+    let user = sqlx::query_as("SELECT users.id AS user_id, users.name AS user_name, users.role
+            FROM user_sessions
+            JOIN users ON users.id = user_sessions.user_id
+            WHERE user_sessions.token_hash = $1 AND user_sessions.expires_at > now();")
+        .bind(hash_token(token))
+        .fetch_optional(db_pool).await?;
+    Ok(user)
+}
+
+/// Returns whether a session was ended
+pub async fn delete_session(db_pool: &PgPool, token: &str) -> anyhow::Result<bool> {
+    let result = sqlx::query("DELETE FROM user_sessions WHERE token_hash = $1;")
+        .bind(hash_token(token))
+        .execute(db_pool).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
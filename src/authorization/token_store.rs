@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use crate::authorization::{Role, Scope};
+
+/// Name given to the token of a legacy auth file containing only a bare token
+const LEGACY_TOKEN_NAME: &str = "default";
+
+/// A named token and the role and/or scopes it grants
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthToken {
+    pub name: String,
+    token: String,
+    pub role: Option<Role>,
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+}
+
+impl AuthToken {
+    /// Scopes of the token's role plus any granted individually
+    pub fn granted_scopes(&self) -> Vec<Scope> {
+        let mut scopes = self.role.map(|role| role.scopes().to_vec()).unwrap_or_default();
+        for scope in &self.scopes {
+            if !scopes.contains(scope) {
+                scopes.push(*scope);
+            }
+        }
+        scopes
+    }
+}
+
+/// Auth file format:
+///
+/// ```toml
+/// [[token]]
+/// name = "mobile-app"
+/// token = "..."
+/// scopes = ["recipe:write", "ingredient:write", "image:write"]
+///
+/// [[token]]
+/// name = "moderation"
+/// token = "..."
+/// role = "admin"
+/// ```
+///
+/// A file containing just a bare token is still accepted, with the admin role.
+#[derive(Debug, Deserialize)]
+struct AuthFile {
+    #[serde(rename = "token")]
+    tokens: Vec<AuthToken>,
+}
+
+/// Tokens loaded from the auth file, reloaded whenever the file changes
+pub struct TokenStore {
+    auth_file: PathBuf,
+    modified: Option<SystemTime>,
+    tokens: Vec<AuthToken>,
+}
+
+impl TokenStore {
+    pub fn new(auth_file: PathBuf) -> Self {
+        Self { auth_file, modified: None, tokens: vec![] }
+    }
+
+    pub fn auth_file(&self) -> &Path {
+        &self.auth_file
+    }
+
+    /// Whether the auth file has changed since the tokens were last loaded
+    pub fn is_stale(&self) -> bool {
+        let modified = fs::metadata(&self.auth_file).and_then(|metadata| metadata.modified()).ok();
+        self.modified.is_none() || modified != self.modified
+    }
+
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let modified = fs::metadata(&self.auth_file)?.modified()?;
+        let contents = fs::read_to_string(&self.auth_file)?;
+        self.tokens = parse_tokens(&contents)?;
+        self.modified = Some(modified);
+        log::info!("Loaded {} auth token(s) from {}", self.tokens.len(), self.auth_file.display());
+        Ok(())
+    }
+
+    /// Find the token matching `presented`, comparing against every token in constant time
+    pub fn find(&self, presented: &str) -> Option<&AuthToken> {
+        let mut found = None;
+        for token in &self.tokens {
+            if bool::from(token.token.as_bytes().ct_eq(presented.as_bytes())) {
+                found = Some(token);
+            }
+        }
+        found
+    }
+}
+
+fn parse_tokens(contents: &str) -> anyhow::Result<Vec<AuthToken>> {
+    if let Ok(auth_file) = toml::from_str::<AuthFile>(contents) {
+        return Ok(auth_file.tokens);
+    }
+
+    let token = contents.trim();
+    if token.is_empty() || token.contains(char::is_whitespace) {
+        anyhow::bail!("Auth file is neither a token list nor a single token");
+    }
+    Ok(vec![AuthToken {
+        name: LEGACY_TOKEN_NAME.to_string(),
+        token: token.to_string(),
+        role: Some(Role::Admin),
+        scopes: vec![],
+    }])
+}
@@ -1,9 +1,17 @@
 mod database;
+mod environment;
+mod ranking;
+mod rate_limit;
 
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::{anyhow, bail};
 use serde::Deserialize;
 
+pub use ranking::RankingWeights;
+pub use rate_limit::{RateLimit, RateLimitConfig};
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub address: String,
@@ -11,8 +19,48 @@ pub struct Config {
     pub database: database::DatabaseConfig,
     pub log_level: log::Level,
     pub auth_file: PathBuf,
+    pub session_lifetime: Duration,
+    pub rate_limit: RateLimitConfig,
+    pub ranking: RankingWeights,
+}
+
+impl Config {
+    /// Check a freshly parsed config before it replaces the running one
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.auth_file.is_file() {
+            bail!("Auth file {} does not exist", self.auth_file.display());
+        }
+        Ok(())
+    }
+
+    /// Settings that differ from `other` but only take effect after a restart
+    pub fn restart_required_changes(&self, other: &Config) -> Vec<&'static str> {
+        let mut changes = vec![];
+        if self.address != other.address {
+            changes.push("address");
+        }
+        if self.database != other.database {
+            changes.push("database");
+        }
+        changes
+    }
+
+    /// Take the reloadable settings from `reloaded`, keeping those that require a restart
+    pub fn apply_reload(&mut self, reloaded: Config) {
+        self.image_folder = reloaded.image_folder;
+        self.log_level = reloaded.log_level;
+        self.auth_file = reloaded.auth_file;
+        self.session_lifetime = reloaded.session_lifetime;
+        self.rate_limit = reloaded.rate_limit;
+        self.ranking = reloaded.ranking;
+    }
 }
 
+/// Options read from the config file.
+///
+/// Any option can be overridden by an environment variable named after its key, prefixed with
+/// `ALACARTE_` and with nested tables separated by `__` - e.g. `ALACARTE_DATABASE__ADDRESS`.
+/// Precedence is command line arguments, then environment variables, then the config file.
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigFile {
     pub address: Option<String>,
@@ -20,22 +68,76 @@ pub struct ConfigFile {
     pub database: database::ConfigFileDatabaseTable,
     pub verbose: Option<log::Level>,
     pub auth_file: Option<PathBuf>,
+    pub session_lifetime_hours: Option<u64>,
+    pub rate_limit: Option<rate_limit::ConfigFileRateLimitTable>,
+    pub ranking: Option<ranking::ConfigFileRankingTable>,
+}
+
+impl ConfigFile {
+    /// Parse config file contents, applying any `ALACARTE_` environment variable overrides. Returns warnings about
+    /// ignored variables to log once logging has started
+    pub fn from_str_with_env(contents: &str) -> anyhow::Result<(Self, Vec<String>)> {
+        Self::from_str_with_vars(contents, std::env::vars())
+    }
+
+    fn from_str_with_vars(contents: &str, vars: impl Iterator<Item = (String, String)>) -> anyhow::Result<(Self, Vec<String>)> {
+        let mut table: toml::Table = toml::from_str(contents)?;
+        let warnings = environment::apply_overrides(&mut table, vars)?;
+        Ok((table.try_into()?, warnings))
+    }
 }
 
+/// A config value given directly, read from a file, or read from an environment variable.
+///
+/// Written as `{ value = "..." }`, `{ path = "..." }` or `{ env = "..." }`. A bare value is
+/// treated as `{ value = ... }`.
 #[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "toml::Value")]
 pub enum ValueOrPath {
-    #[serde(rename = "value")]
     Value(String),
-    #[serde(rename = "path")]
-    Path(PathBuf)
+    Path(PathBuf),
+    Env(String),
 }
 
 impl ValueOrPath {
     pub fn try_convert_to_value(self) -> anyhow::Result<String> {
         let value = match self {
-            ValueOrPath::Value(value) => Ok(value),
-            ValueOrPath::Path(path) => fs::read_to_string(&path)
-        }?;
+            ValueOrPath::Value(value) => value,
+            ValueOrPath::Path(path) => fs::read_to_string(&path)?,
+            ValueOrPath::Env(variable) => std::env::var(&variable)
+                .map_err(|err| anyhow!("Failed to read environment variable {} - {}", variable, err))?,
+        };
         Ok(value)
     }
-}
\ No newline at end of file
+}
+
+impl TryFrom<toml::Value> for ValueOrPath {
+    type Error = anyhow::Error;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        let table = match value {
+            toml::Value::Table(table) => table,
+            toml::Value::String(value) => return Ok(ValueOrPath::Value(value)),
+            toml::Value::Integer(value) => return Ok(ValueOrPath::Value(value.to_string())),
+            toml::Value::Float(value) => return Ok(ValueOrPath::Value(value.to_string())),
+            toml::Value::Boolean(value) => return Ok(ValueOrPath::Value(value.to_string())),
+            other => bail!("Expected a value, path or env table but found {}", other.type_str()),
+        };
+
+        if table.len() != 1 {
+            bail!("Expected exactly one of 'value', 'path' or 'env'");
+        }
+        let (key, value) = table.into_iter().next().expect("table has one entry");
+        let value = match value {
+            toml::Value::String(value) => value,
+            other => bail!("Expected '{}' to be a string but found {}", key, other.type_str()),
+        };
+
+        match key.as_str() {
+            "value" => Ok(ValueOrPath::Value(value)),
+            "path" => Ok(ValueOrPath::Path(PathBuf::from(value))),
+            "env" => Ok(ValueOrPath::Env(value)),
+            _ => bail!("Unknown key '{}' - expected one of 'value', 'path' or 'env'", key),
+        }
+    }
+}
@@ -1,31 +1,235 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use anyhow::{anyhow, bail};
 use serde::Deserialize;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgSslMode};
 use crate::config::ValueOrPath;
 
+const DEFAULT_PORT: u16 = 5432;
+const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+const DEFAULT_MIN_CONNECTIONS: u32 = 0;
+const DEFAULT_ACQUIRE_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 600;
+const DEFAULT_CONNECT_RETRIES: u32 = 5;
+const DEFAULT_CONNECT_RETRY_DELAY_MS: u64 = 500;
+
 #[derive(Debug, Clone)]
 pub struct DatabaseConfig {
-    pub name: String,
-    pub username: String,
-    pub password: String,
-    pub address: String
+    pub connection: DatabaseConnection,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub statement_timeout: Option<Duration>,
+    pub ssl_mode: Option<PgSslMode>,
+    pub ssl_root_cert: Option<PathBuf>,
+    /// Number of times to retry the initial connection before giving up
+    pub connect_retries: u32,
+    /// Delay before the first retry, doubled after every failed attempt
+    pub connect_retry_delay: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DatabaseConnection {
+    Url(String),
+    Parts {
+        name: String,
+        username: String,
+        password: String,
+        address: String,
+    },
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ConfigFileDatabaseTable {
-    pub name: ValueOrPath,
-    pub username: ValueOrPath,
-    pub password: ValueOrPath,
-    pub address: ValueOrPath
+    pub url: Option<ValueOrPath>,
+    pub name: Option<ValueOrPath>,
+    pub username: Option<ValueOrPath>,
+    pub password: Option<ValueOrPath>,
+    pub address: Option<ValueOrPath>,
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    pub acquire_timeout_secs: Option<u64>,
+    /// Set to 0 to never close idle connections
+    pub idle_timeout_secs: Option<u64>,
+    pub statement_timeout_ms: Option<u64>,
+    /// One of `disable`, `allow`, `prefer`, `require`, `verify-ca` or `verify-full`
+    pub ssl_mode: Option<String>,
+    pub ssl_root_cert: Option<PathBuf>,
+    pub connect_retries: Option<u32>,
+    pub connect_retry_delay_ms: Option<u64>,
 }
 
 impl TryFrom<ConfigFileDatabaseTable> for DatabaseConfig {
     type Error = anyhow::Error;
 
     fn try_from(value: ConfigFileDatabaseTable) -> Result<Self, Self::Error> {
-        let name = value.name.try_convert_to_value()?;
-        let username = value.username.try_convert_to_value()?;
-        let password = value.password.try_convert_to_value()?;
-        let address = value.address.try_convert_to_value()?;
+        let connection = match value.url {
+            Some(url) => DatabaseConnection::Url(url.try_convert_to_value()?.trim().to_string()),
+            None => {
+                let name = required_part(value.name, "name")?;
+                let username = required_part(value.username, "username")?;
+                let password = required_part(value.password, "password")?;
+                let address = required_part(value.address, "address")?;
+                DatabaseConnection::Parts { name, username, password, address }
+            }
+        };
+
+        let max_connections = value.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS);
+        let min_connections = value.min_connections.unwrap_or(DEFAULT_MIN_CONNECTIONS);
+        if min_connections > max_connections {
+            bail!("Database min_connections ({}) is greater than max_connections ({})", min_connections, max_connections);
+        }
+
+        let acquire_timeout = Duration::from_secs(value.acquire_timeout_secs.unwrap_or(DEFAULT_ACQUIRE_TIMEOUT_SECS));
+        let idle_timeout = match value.idle_timeout_secs.unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+        let statement_timeout = value.statement_timeout_ms.map(Duration::from_millis);
+
+        let ssl_mode = match value.ssl_mode {
+            Some(ssl_mode) => Some(PgSslMode::from_str(&ssl_mode).map_err(|err| anyhow!("Invalid database ssl_mode '{}' - {}", ssl_mode, err))?),
+            None => None,
+        };
+
+        let connect_retries = value.connect_retries.unwrap_or(DEFAULT_CONNECT_RETRIES);
+        let connect_retry_delay = Duration::from_millis(value.connect_retry_delay_ms.unwrap_or(DEFAULT_CONNECT_RETRY_DELAY_MS));
+
+        Ok(DatabaseConfig {
+            connection,
+            max_connections,
+            min_connections,
+            acquire_timeout,
+            idle_timeout,
+            statement_timeout,
+            ssl_mode,
+            ssl_root_cert: value.ssl_root_cert,
+            connect_retries,
+            connect_retry_delay,
+        })
+    }
+}
+
+impl PartialEq for DatabaseConfig {
+    fn eq(&self, other: &Self) -> bool {
+        // PgSslMode doesn't implement PartialEq, but is a plain enum
+        self.connection == other.connection
+            && self.max_connections == other.max_connections
+            && self.min_connections == other.min_connections
+            && self.acquire_timeout == other.acquire_timeout
+            && self.idle_timeout == other.idle_timeout
+            && self.statement_timeout == other.statement_timeout
+            && self.ssl_mode.map(|mode| mode as u8) == other.ssl_mode.map(|mode| mode as u8)
+            && self.ssl_root_cert == other.ssl_root_cert
+            && self.connect_retries == other.connect_retries
+            && self.connect_retry_delay == other.connect_retry_delay
+    }
+}
+
+fn required_part(part: Option<ValueOrPath>, name: &str) -> anyhow::Result<String> {
+    match part {
+        Some(part) => Ok(part.try_convert_to_value()?.trim().to_string()),
+        None => bail!("Database {} must be provided when no database url is set", name),
+    }
+}
+
+impl DatabaseConfig {
+    pub fn connect_options(&self) -> anyhow::Result<PgConnectOptions> {
+        let mut options = match &self.connection {
+            DatabaseConnection::Url(url) => PgConnectOptions::from_str(url)?,
+            DatabaseConnection::Parts { name, username, password, address } => {
+                let (host, port) = split_address(address)?;
+                PgConnectOptions::new()
+                    .host(host)
+                    .port(port)
+                    .username(username)
+                    .password(password)
+                    .database(name)
+            }
+        };
+
+        if let Some(ssl_mode) = self.ssl_mode {
+            options = options.ssl_mode(ssl_mode);
+        }
+        if let Some(ssl_root_cert) = &self.ssl_root_cert {
+            options = options.ssl_root_cert(ssl_root_cert);
+        }
+        if let Some(statement_timeout) = self.statement_timeout {
+            options = options.options([("statement_timeout", format!("{}ms", statement_timeout.as_millis()))]);
+        }
+
+        Ok(options)
+    }
+
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+    }
+
+    /// Host and port of the database, safe to log
+    pub fn display_address(&self) -> String {
+        match self.connect_options() {
+            Ok(options) => format!("{}:{}", options.get_host(), options.get_port()),
+            Err(_) => String::from("<invalid database address>"),
+        }
+    }
+}
+
+/// Host and port of `host`, `host:port`, an IPv6 literal, or a bracketed IPv6 literal with an optional port such as
+/// `[::1]:5432`
+fn split_address(address: &str) -> anyhow::Result<(&str, u16)> {
+    if let Some(bracketed) = address.strip_prefix('[') {
+        let (host, rest) = bracketed.split_once(']').ok_or_else(|| anyhow!("Invalid database address '{}' - missing ']'", address))?;
+        return match rest {
+            "" => Ok((host, DEFAULT_PORT)),
+            rest => match rest.strip_prefix(':') {
+                Some(port) => Ok((host, parse_port(port)?)),
+                None => bail!("Invalid database address '{}' - expected ':' after ']'", address),
+            },
+        };
+    }
 
-        Ok(DatabaseConfig{ name, username, password , address })
+    match address.rsplit_once(':') {
+        // More than one colon is an unbracketed IPv6 literal, which can't carry a port
+        Some((host, _)) if host.contains(':') => Ok((address, DEFAULT_PORT)),
+        Some((host, port)) => Ok((host, parse_port(port)?)),
+        None => Ok((address, DEFAULT_PORT)),
     }
-}
\ No newline at end of file
+}
+
+fn parse_port(port: &str) -> anyhow::Result<u16> {
+    port.parse().map_err(|err| anyhow!("Invalid database port '{}' - {}", port, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_host_and_port() {
+        assert_eq!(split_address("localhost:6543").unwrap(), ("localhost", 6543));
+        assert_eq!(split_address("localhost").unwrap(), ("localhost", DEFAULT_PORT));
+        assert_eq!(split_address("127.0.0.1:5432").unwrap(), ("127.0.0.1", 5432));
+    }
+
+    #[test]
+    fn splits_ipv6_literals() {
+        assert_eq!(split_address("[::1]:6543").unwrap(), ("::1", 6543));
+        assert_eq!(split_address("[::1]").unwrap(), ("::1", DEFAULT_PORT));
+        assert_eq!(split_address("::1").unwrap(), ("::1", DEFAULT_PORT));
+        assert_eq!(split_address("fe80::1:2").unwrap(), ("fe80::1:2", DEFAULT_PORT));
+    }
+
+    #[test]
+    fn rejects_bad_addresses() {
+        assert!(split_address("localhost:port").is_err());
+        assert!(split_address("[::1").is_err());
+        assert!(split_address("[::1]5432").is_err());
+        assert!(split_address("[::1]:99999").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+/// Prefix of environment variables that override config file options
+pub const ENV_PREFIX: &str = "ALACARTE_";
+
+/// Separator between nested table names in an override variable, e.g. `ALACARTE_DATABASE__ADDRESS`
+const TABLE_SEPARATOR: &str = "__";
+
+/// Options that aren't strings, as dotted paths where `*` matches any key. Overrides of these are read as TOML
+/// values, every other override is kept exactly as given so passwords like `0x1F` or `007` survive
+const TYPED_KEYS: [&str; 12] = [
+    "session_lifetime_hours",
+    "database.max_connections",
+    "database.min_connections",
+    "database.acquire_timeout_secs",
+    "database.idle_timeout_secs",
+    "database.statement_timeout_ms",
+    "database.connect_retries",
+    "database.connect_retry_delay_ms",
+    "rate_limit.enabled",
+    "rate_limit.*.capacity",
+    "rate_limit.*.per_minute",
+    "ranking.*",
+];
+
+/// Overlay every `ALACARTE_` variable in `vars` onto the parsed config file table, returning warnings about
+/// variables that were ignored. They're returned rather than logged as the config is read before logging starts
+pub fn apply_overrides(table: &mut toml::Table, vars: impl Iterator<Item = (String, String)>) -> anyhow::Result<Vec<String>> {
+    let mut warnings = vec![];
+    for (name, value) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+
+        let path = key.split(TABLE_SEPARATOR).map(|segment| segment.to_lowercase()).collect::<Vec<String>>();
+        if path.iter().any(|segment| segment.is_empty()) {
+            warnings.push(format!("Ignoring malformed config override variable {}", name));
+            continue;
+        }
+
+        let value = match is_typed(&path) {
+            true => parse_value(value),
+            false => toml::Value::String(value),
+        };
+        apply_override(table, &path, value, &name)?;
+    }
+    Ok(warnings)
+}
+
+fn apply_override(table: &mut toml::Table, path: &[String], value: toml::Value, name: &str) -> anyhow::Result<()> {
+    let (key, parents) = path.split_last().expect("override path is never empty");
+
+    let mut current = table;
+    for parent in parents {
+        let entry = current.entry(parent.clone()).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        current = match entry {
+            toml::Value::Table(table) => table,
+            _ => anyhow::bail!("Config override {} targets '{}', which is not a table", name, parent),
+        };
+    }
+
+    current.insert(key.clone(), value);
+    Ok(())
+}
+
+fn is_typed(path: &[String]) -> bool {
+    TYPED_KEYS.iter().any(|typed| {
+        let typed = typed.split('.').collect::<Vec<&str>>();
+        typed.len() == path.len() && typed.iter().zip(path).all(|(typed, segment)| *typed == "*" || typed == segment)
+    })
+}
+
+/// Interpret a variable as a TOML number or boolean, falling back to a plain string, which then fails to
+/// deserialize with a message naming the option
+fn parse_value(value: String) -> toml::Value {
+    let parsed = toml::from_str::<toml::Table>(&format!("value = {}", value));
+    match parsed.ok().and_then(|mut table| table.remove("value")) {
+        Some(parsed @ (toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_))) => parsed,
+        _ => toml::Value::String(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ConfigFile, ValueOrPath};
+    use crate::RateLimitConfig;
+
+    const FILE: &str = "
+        session_lifetime_hours = 12
+        [database]
+        password = 'from-file'
+        [rate_limit.read]
+        capacity = 10
+        per_minute = 10
+    ";
+
+    fn read(contents: &str, vars: &[(&str, &str)]) -> (ConfigFile, Vec<String>) {
+        let vars = vars.iter().map(|(name, value)| (name.to_string(), value.to_string()));
+        ConfigFile::from_str_with_vars(contents, vars).unwrap()
+    }
+
+    fn password(config: ConfigFile) -> String {
+        config.database.password.map(ValueOrPath::try_convert_to_value).unwrap().unwrap()
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let (config, _) = read(FILE, &[("ALACARTE_SESSION_LIFETIME_HOURS", "48"), ("ALACARTE_DATABASE__PASSWORD", "from-env")]);
+        assert_eq!(config.session_lifetime_hours, Some(48));
+        assert_eq!(password(config), "from-env");
+    }
+
+    #[test]
+    fn file_is_used_without_an_override() {
+        let (config, _) = read(FILE, &[("UNRELATED", "1")]);
+        assert_eq!(config.session_lifetime_hours, Some(12));
+        assert_eq!(password(config), "from-file");
+    }
+
+    #[test]
+    fn defaults_apply_without_file_or_override() {
+        let (config, _) = read("[database]", &[]);
+        assert_eq!(config.session_lifetime_hours, None);
+        assert!(config.rate_limit.is_none());
+        assert_eq!(RateLimitConfig::default().read.map(|limit| limit.per_minute), Some(300));
+    }
+
+    #[test]
+    fn string_options_keep_the_value_as_given() {
+        for value in ["0x1F", "1e5", "007", "true", "[1, 2]", "a = b"] {
+            let (config, _) = read(FILE, &[("ALACARTE_DATABASE__PASSWORD", value)]);
+            assert_eq!(password(config), value);
+        }
+    }
+
+    #[test]
+    fn typed_options_are_parsed() {
+        let (config, _) = read(FILE, &[
+            ("ALACARTE_RATE_LIMIT__ENABLED", "false"),
+            ("ALACARTE_RATE_LIMIT__READ__PER_MINUTE", "20"),
+            ("ALACARTE_RANKING__VIEWS", "2"),
+            ("ALACARTE_RANKING__RECENCY", "0.5"),
+        ]);
+        let rate_limit = config.rate_limit.unwrap();
+        assert_eq!(rate_limit.enabled, Some(false));
+        assert_eq!(rate_limit.read.map(|limit| limit.per_minute), Some(20));
+        let ranking = config.ranking.unwrap();
+        assert_eq!(ranking.views, Some(2.0));
+        assert_eq!(ranking.recency, Some(0.5));
+    }
+
+    #[test]
+    fn bad_typed_values_fail_to_read() {
+        let vars = [("ALACARTE_SESSION_LIFETIME_HOURS".to_string(), "soon".to_string())];
+        assert!(ConfigFile::from_str_with_vars(FILE, vars.into_iter()).is_err());
+    }
+
+    #[test]
+    fn malformed_variables_are_returned_as_warnings() {
+        let (config, warnings) = read(FILE, &[("ALACARTE_DATABASE__", "x")]);
+        assert_eq!(warnings, vec!["Ignoring malformed config override variable ALACARTE_DATABASE__".to_string()]);
+        assert_eq!(password(config), "from-file");
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::bail;
+use serde::Deserialize;
+
+/// How much each signal counts towards a recipe's score. Every signal scores between 0 and 1 before weighting,
+/// so a weight is the most a signal can add
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankingWeights {
+    pub image: f64,
+    pub method: f64,
+    pub ingredients: f64,
+    pub rating: f64,
+    pub favorites: f64,
+    pub recency: f64,
+    pub views: f64,
+}
+
+const DEFAULT_WEIGHTS: RankingWeights = RankingWeights {
+    image: 1.0,
+    method: 0.5,
+    ingredients: 0.5,
+    rating: 2.0,
+    favorites: 1.5,
+    recency: 1.0,
+    views: 1.0,
+};
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        DEFAULT_WEIGHTS
+    }
+}
+
+/// Weights to override - missing weights keep their default, and 0 turns a signal off
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfigFileRankingTable {
+    pub image: Option<f64>,
+    pub method: Option<f64>,
+    pub ingredients: Option<f64>,
+    pub rating: Option<f64>,
+    pub favorites: Option<f64>,
+    pub recency: Option<f64>,
+    pub views: Option<f64>,
+}
+
+impl TryFrom<ConfigFileRankingTable> for RankingWeights {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ConfigFileRankingTable) -> Result<Self, Self::Error> {
+        let weights = RankingWeights {
+            image: value.image.unwrap_or(DEFAULT_WEIGHTS.image),
+            method: value.method.unwrap_or(DEFAULT_WEIGHTS.method),
+            ingredients: value.ingredients.unwrap_or(DEFAULT_WEIGHTS.ingredients),
+            rating: value.rating.unwrap_or(DEFAULT_WEIGHTS.rating),
+            favorites: value.favorites.unwrap_or(DEFAULT_WEIGHTS.favorites),
+            recency: value.recency.unwrap_or(DEFAULT_WEIGHTS.recency),
+            views: value.views.unwrap_or(DEFAULT_WEIGHTS.views),
+        };
+
+        let all = [weights.image, weights.method, weights.ingredients, weights.rating, weights.favorites, weights.recency, weights.views];
+        if all.iter().any(|weight| !weight.is_finite() || *weight < 0.0) {
+            bail!("Ranking weights must be finite and not negative");
+        }
+        Ok(weights)
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::bail;
+use serde::Deserialize;
+
+/// A token bucket holding up to `capacity` requests, refilled at `per_minute` requests a minute
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub per_minute: u32,
+}
+
+/// Per client request budgets. A budget of `None` is unlimited
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    /// GET requests, per client address
+    pub read: Option<RateLimit>,
+    /// Any other request, per client address and per authorized caller
+    pub write: Option<RateLimit>,
+    /// Rejected credentials and failed logins, per client address
+    pub failed_auth: Option<RateLimit>,
+}
+
+const DEFAULT_READ: RateLimit = RateLimit { capacity: 300, per_minute: 300 };
+const DEFAULT_WRITE: RateLimit = RateLimit { capacity: 60, per_minute: 30 };
+const DEFAULT_FAILED_AUTH: RateLimit = RateLimit { capacity: 10, per_minute: 2 };
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self { read: Some(DEFAULT_READ), write: Some(DEFAULT_WRITE), failed_auth: Some(DEFAULT_FAILED_AUTH) }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConfigFileRateLimitTable {
+    /// Set to false to turn off rate limiting entirely
+    pub enabled: Option<bool>,
+    pub read: Option<RateLimit>,
+    pub write: Option<RateLimit>,
+    pub failed_auth: Option<RateLimit>,
+}
+
+impl TryFrom<ConfigFileRateLimitTable> for RateLimitConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ConfigFileRateLimitTable) -> Result<Self, Self::Error> {
+        if !value.enabled.unwrap_or(true) {
+            return Ok(RateLimitConfig { read: None, write: None, failed_auth: None });
+        }
+
+        let config = RateLimitConfig {
+            read: Some(value.read.unwrap_or(DEFAULT_READ)),
+            write: Some(value.write.unwrap_or(DEFAULT_WRITE)),
+            failed_auth: Some(value.failed_auth.unwrap_or(DEFAULT_FAILED_AUTH)),
+        };
+
+        // A bucket that never refills would lock a client out for good, turn rate limiting off instead
+        let limits = [config.read, config.write, config.failed_auth];
+        if limits.iter().flatten().any(|limit| limit.per_minute == 0) {
+            bail!("Rate limits must refill at least one request a minute");
+        }
+        Ok(config)
+    }
+}
@@ -1,10 +1,16 @@
+pub mod client_address;
+pub mod etag;
 pub mod http_codec;
 pub mod header;
+pub mod merge_patch;
+pub mod query;
 pub mod request_line;
 pub mod status_line;
 pub mod responses;
 
+pub use client_address::ClientAddress;
 pub use header::Header;
+pub use query::QueryParams;
 pub use request_line::RequestLine;
 pub use status_line::StatusLine;
 pub use http_codec::HttpCodec;
\ No newline at end of file
@@ -0,0 +1,11 @@
+use std::net::IpAddr;
+
+/// Address of the peer a request was received from, stored in the request's extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddress(pub IpAddr);
+
+impl ClientAddress {
+    pub fn of<T>(request: &http::Request<T>) -> Option<IpAddr> {
+        request.extensions().get::<ClientAddress>().map(|address| address.0)
+    }
+}
@@ -0,0 +1,16 @@
+use http::HeaderValue;
+
+/// Strong entity tag for a version number
+pub fn etag(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Whether an If-Match header allows a change to the resource tagged `etag`.
+/// Uses strong comparison, so weak tags never match
+pub fn if_match_allows(if_match: &HeaderValue, etag: &str) -> bool {
+    let if_match = match if_match.to_str() {
+        Ok(if_match) => if_match.trim(),
+        Err(_) => return false,
+    };
+    if_match == "*" || if_match.split(',').map(str::trim).any(|tag| tag == etag)
+}
@@ -54,7 +54,8 @@ impl HttpCodec {
         let mut header_map = HashMap::new();
         _ = self.reader.read_line(&mut buffer)?;
         while buffer != "\r\n" {
-            let header = Header::try_from(buffer.trim_end_matches("\r\n").to_lowercase().to_string())?;
+            // Header names are case-insensitive and normalised by HeaderName, but values are not
+            let header = Header::try_from(buffer.trim_end_matches("\r\n").to_string())?;
             header_map.insert(header.key, header.value);
 
             buffer.clear();
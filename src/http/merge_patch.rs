@@ -0,0 +1,25 @@
+use serde_json::Value;
+
+/// Apply a JSON Merge Patch (RFC 7396) to `target` - null removes a member, objects merge and anything else replaces
+pub fn apply_merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                target.remove(key);
+            },
+            value => apply_merge_patch(target.entry(key.clone()).or_insert(Value::Null), value),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use http::Uri;
+
+/// Decoded `key=value` pairs of a request's query string
+pub struct QueryParams {
+    params: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    pub fn from_uri(uri: &Uri) -> Self {
+        let params = form_urlencoded::parse(uri.query().unwrap_or("").as_bytes())
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        Self { params }
+    }
+
+    /// First value given for `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(param, _)| param == key).map(|(_, value)| value.as_str())
+    }
+
+    /// Every value given for a repeated `key`
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.params.iter().filter(|(param, _)| param == key).map(|(_, value)| value.as_str()).collect()
+    }
+
+    /// Parse the first value given for `key`, failing with a message naming the parameter
+    pub fn parse<T: std::str::FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        match self.get(key) {
+            Some(value) => value.parse().map(Some).map_err(|_| format!("Invalid value '{}' for query parameter '{}'", value, key)),
+            None => Ok(None),
+        }
+    }
+
+    /// The query string with `key` set to `value`, keeping every other parameter
+    pub fn to_query_with(&self, key: &str, value: &str) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        for (param, param_value) in self.params.iter().filter(|(param, _)| param != key) {
+            query.append_pair(param, param_value);
+        }
+        query.append_pair(key, value);
+        query.finish()
+    }
+}
@@ -1,6 +1,9 @@
 use http::Response;
 use serde::Serialize;
 
+/// Longest wait given in Retry-After, a day
+const MAX_RETRY_AFTER_SECONDS: u64 = 24 * 60 * 60;
+
 pub fn empty_ok() -> Response<Vec<u8>> {
     http::Response::builder()
         .status(http::status::StatusCode::OK)
@@ -37,13 +40,23 @@ pub fn bad_request_response() -> Response<Vec<u8>> {
 }
 
 pub fn bad_request_response_with_message(message: &str) -> Response<Vec<u8>> {
-    let message = message.to_string();
-    let message = ResponseMessage { message };
-    let message = serde_json::to_vec(&message).unwrap_or({
-        log::error!("Failed to serialize ResponseMessage '{}'", message.message);
-        Vec::new()
-    });
-    http::Response::builder().status(http::status::StatusCode::BAD_REQUEST).body(message).expect("error building bad request response")
+    message_response(http::status::StatusCode::BAD_REQUEST, message)
+}
+
+pub fn bad_request_response_with_challenge(challenge: &str, message: &str) -> Response<Vec<u8>> {
+    challenge_response(http::status::StatusCode::BAD_REQUEST, challenge, message)
+}
+
+pub fn conflict_response_with_message(message: &str) -> Response<Vec<u8>> {
+    message_response(http::status::StatusCode::CONFLICT, message)
+}
+
+pub fn precondition_failed_response_with_message(message: &str) -> Response<Vec<u8>> {
+    message_response(http::status::StatusCode::PRECONDITION_FAILED, message)
+}
+
+pub fn precondition_required_response_with_message(message: &str) -> Response<Vec<u8>> {
+    message_response(http::status::StatusCode::PRECONDITION_REQUIRED, message)
 }
 
 #[derive(Debug, Serialize)]
@@ -61,4 +74,49 @@ pub fn method_not_allowed_response() -> Response<Vec<u8>> {
 
 pub fn unauthorized_response() -> Response<Vec<u8>> {
     http::Response::builder().status(http::status::StatusCode::UNAUTHORIZED).body(Vec::new()).expect("error building unauthorized response")
-}
\ No newline at end of file
+}
+
+pub fn forbidden_response_with_message(message: &str) -> Response<Vec<u8>> {
+    message_response(http::status::StatusCode::FORBIDDEN, message)
+}
+
+pub fn too_many_requests_response(retry_after: std::time::Duration) -> Response<Vec<u8>> {
+    // Retry-After is whole seconds, so round up rather than invite an early retry
+    let retry_after = retry_after.as_secs().saturating_add(u64::from(retry_after.subsec_nanos() > 0)).min(MAX_RETRY_AFTER_SECONDS);
+    http::Response::builder()
+        .status(http::status::StatusCode::TOO_MANY_REQUESTS)
+        .header(http::header::RETRY_AFTER, retry_after)
+        .body(Vec::new())
+        .expect("error building too many requests response")
+}
+
+pub fn unauthorized_response_with_challenge(challenge: &str, message: &str) -> Response<Vec<u8>> {
+    challenge_response(http::status::StatusCode::UNAUTHORIZED, challenge, message)
+}
+
+pub fn forbidden_response_with_challenge(challenge: &str, message: &str) -> Response<Vec<u8>> {
+    challenge_response(http::status::StatusCode::FORBIDDEN, challenge, message)
+}
+
+fn challenge_response(status: http::StatusCode, challenge: &str, message: &str) -> Response<Vec<u8>> {
+    let mut response = message_response(status, message);
+    if let Ok(challenge) = http::HeaderValue::from_str(challenge) {
+        response.headers_mut().insert(http::header::WWW_AUTHENTICATE, challenge);
+    }
+    response
+}
+
+/// A response with `status` and a JSON body of `{ "message": message }`
+fn message_response(status: http::StatusCode, message: &str) -> Response<Vec<u8>> {
+    let message = ResponseMessage { message: message.to_string() };
+    let body = serde_json::to_vec(&message).unwrap_or_else(|_| {
+        log::error!("Failed to serialize ResponseMessage '{}'", message.message);
+        Vec::new()
+    });
+    http::Response::builder()
+        .status(status)
+        .header("Content-Length", body.len())
+        .header("Content-Type", "application/json")
+        .body(body)
+        .expect("error building message response")
+}
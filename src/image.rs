@@ -1,39 +1,42 @@
 mod post_image;
 
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use futures::executor::block_on;
 use http::{Method, Request, Response};
-use crate::authorization::Authorization;
+use sqlx::{PgExecutor, PgPool};
+use crate::audit::{self, Action, AuditEntry};
+use crate::authorization::{Authorization, Identity};
 use crate::Config;
 use crate::http::responses;
-use crate::http::responses::unauthorized_response;
 
 pub fn can_handle_request(request: &Request<Vec<u8>>) -> bool {
     match *request.method() {
         Method::GET => request.uri().path().starts_with("/image/"),
         Method::POST => request.uri().path() == "/image",
+        Method::DELETE => request.uri().path().starts_with("/image/"),
         _ => false,
     }
 
 }
 
-pub fn handle_request(request: Request<Vec<u8>>, config: &Config, auth_handler: &Authorization) -> Response<Vec<u8>> {
+pub fn handle_request(request: Request<Vec<u8>>, config: &Config, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
     match *request.method() {
         Method::GET => handle_get_request(&request, config),
-        Method::POST => handle_post_request(&request, config, auth_handler),
+        Method::POST => handle_post_request(&request, config, db_pool, auth_handler),
+        Method::DELETE => handle_delete_request(&request, config, db_pool, auth_handler),
         _ => responses::method_not_allowed_response()
     }
 }
 
 fn handle_get_request(request: &Request<Vec<u8>>, config: &Config) -> Response<Vec<u8>> {
-    let bad_get_strings = vec!["..", "$", "~"];
-    for bad_string in bad_get_strings {
-        if request.uri().path().contains(bad_string) {
-            log::info!("Bad request - uri contained forbidden string '{}'", bad_string);
-            return responses::bad_request_response()
-        }
+    if !is_safe_image_uri(request.uri()) {
+        return responses::bad_request_response()
     }
 
-    let image_path = resolve_image_path(request.uri(), &config.image_folder);
+    let image_path = match resolve_image_path(request.uri(), &config.image_folder) {
+        Some(image_path) => image_path,
+        None => return responses::bad_request_response(),
+    };
     log::debug!("Requested image path '{}'", image_path.display());
     if !image_path.exists() || !image_path.is_file() {
         log::debug!("Returning not found response");
@@ -60,20 +63,151 @@ fn handle_get_request(request: &Request<Vec<u8>>, config: &Config) -> Response<V
     }
 }
 
-fn handle_post_request(request: &Request<Vec<u8>>, config: &Config, auth_handler: &Authorization) -> Response<Vec<u8>> {
-    match auth_handler.authenticate_request(request) {
-        Ok(_) => (),
-        Err(_) => {
-            return unauthorized_response();
+fn handle_post_request(request: &Request<Vec<u8>>, config: &Config, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
+    let identity = match auth_handler.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    log::info!("Uploading image for {}", identity);
+    let response = post_image::handle_post_request(request, config);
+    let location = match response.headers().get(http::header::LOCATION).and_then(|location| location.to_str().ok()) {
+        Some(location) => location,
+        None => return response,
+    };
+
+    // An upload that can't be audited is taken back down rather than kept unrecorded
+    if let Err(err) = block_on(record_image_change(db_pool, &identity, location, Action::Create)) {
+        log::error!("Failed to write audit log entry for uploading image '{}' - {}", location, err);
+        let uploaded_path = location.parse::<http::Uri>().ok().and_then(|uri| resolve_image_path(&uri, &config.image_folder));
+        if let Some(Err(err)) = uploaded_path.map(std::fs::remove_file) {
+            log::error!("Error removing unaudited image '{}' - {}", location, err);
+        }
+        return responses::internal_server_error_response();
+    }
+    response
+}
+
+fn handle_delete_request(request: &Request<Vec<u8>>, config: &Config, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
+    let identity = match auth_handler.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
         }
+    };
+
+    if !is_safe_image_uri(request.uri()) {
+        return responses::bad_request_response()
     }
 
-    post_image::handle_post_request(request, config)
+    let image_path = match resolve_image_path(request.uri(), &config.image_folder) {
+        Some(image_path) => image_path,
+        None => return responses::bad_request_response(),
+    };
+    if !image_path.is_file() {
+        return responses::not_found_response();
+    }
+
+    match block_on(delete_image(&image_path, request.uri().path(), db_pool, &identity)) {
+        Ok(_) => {
+            log::info!("Deleted image '{}' for {}", image_path.display(), identity);
+            responses::empty_ok()
+        },
+        Err(err) => {
+            log::error!("Error deleting image '{}' - {}", image_path.display(), err);
+            responses::internal_server_error_response()
+        }
+    }
 }
 
-fn resolve_image_path(uri: &http::Uri, image_folder: &Path) -> PathBuf {
-    let trim_length = "/image/".len();
-    let mut root = PathBuf::from(image_folder);
-    root.push(&uri.path()[trim_length..]);
-    root
+/// Removes the image file once its audit log entry is written, and only keeps the entry if the file was removed
+async fn delete_image(image_path: &Path, image_uri: &str, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<()> {
+    let mut tx = db_pool.begin().await?;
+    record_image_change(&mut *tx, identity, image_uri, Action::Delete).await?;
+    std::fs::remove_file(image_path)?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn record_image_change<'e>(executor: impl PgExecutor<'e>, identity: &Identity, image_uri: &str, action: Action) -> anyhow::Result<()> {
+    let image = serde_json::json!({ "image_uri": image_uri });
+    let (before, after) = match action {
+        Action::Delete => (Some(image), None),
+        _ => (None, Some(image)),
+    };
+    audit::record(executor, AuditEntry {
+        actor: identity,
+        resource: "image",
+        resource_id: image_uri.trim_start_matches("/image/").to_string(),
+        action,
+        before,
+        after,
+    }).await
+}
+
+fn is_safe_image_uri(uri: &http::Uri) -> bool {
+    let bad_strings = vec!["..", "$", "~"];
+    for bad_string in bad_strings {
+        if uri.path().contains(bad_string) {
+            log::info!("Bad request - uri contained forbidden string '{}'", bad_string);
+            return false
+        }
+    }
+    true
+}
+
+/// The file in the image folder named by the uri. Only a single plain file name is accepted - nested, absolute or
+/// percent-encoded paths could otherwise reach outside the folder
+fn resolve_image_path(uri: &http::Uri, image_folder: &Path) -> Option<PathBuf> {
+    let file_name = uri.path().strip_prefix("/image/")?;
+    if file_name.contains(['%', '\\']) {
+        log::info!("Bad request - image name '{}' contained an encoded or escaped character", file_name);
+        return None;
+    }
+
+    let mut components = Path::new(file_name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if name == file_name => Some(image_folder.join(name)),
+        _ => {
+            log::info!("Bad request - image name '{}' is not a plain file name", file_name);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(path: &str) -> Option<PathBuf> {
+        resolve_image_path(&path.parse::<http::Uri>().unwrap(), Path::new("/srv/images"))
+    }
+
+    #[test]
+    fn resolves_plain_file_names_in_the_folder() {
+        assert_eq!(resolve("/image/abc.jpg"), Some(PathBuf::from("/srv/images/abc.jpg")));
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        assert_eq!(resolve("/image//etc/passwd"), None);
+        assert_eq!(resolve("/image/"), None);
+    }
+
+    #[test]
+    fn rejects_nested_and_parent_paths() {
+        assert_eq!(resolve("/image/a/b.jpg"), None);
+        assert_eq!(resolve("/image/../secret"), None);
+        assert_eq!(resolve("/image/."), None);
+        assert_eq!(resolve("/image/abc.jpg/"), None);
+    }
+
+    #[test]
+    fn rejects_encoded_paths() {
+        assert_eq!(resolve("/image/%2e%2e"), None);
+        assert_eq!(resolve("/image/%2e%2e%2fsecret"), None);
+        assert_eq!(resolve("/image/..%2fsecret"), None);
+    }
 }
\ No newline at end of file
@@ -1,11 +1,12 @@
 mod get_all_ingredients;
+mod merge_ingredient;
 mod post_ingredient;
 
 use futures::executor::block_on;
 use http::{Method, Request, Response};
 use sqlx::PgPool;
 use crate::authorization::Authorization;
-use crate::http::responses::{bad_request_response, method_not_allowed_response, unauthorized_response};
+use crate::http::responses::{bad_request_response, internal_server_error_response, method_not_allowed_response};
 use crate::ingredient::get_all_ingredients::get_all_ingredients;
 use crate::recipe::chunk_url;
 
@@ -32,31 +33,51 @@ fn handle_get_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<
 
 fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
     let url_chunks = chunk_url(request.uri());
+    if let ["ingredient", ingredient_id, "merge"] = url_chunks.as_slice() {
+        return handle_merge_request(request, ingredient_id, db_pool, authorization);
+    }
     if url_chunks.len() != 1 {
         log::info!("Bad request - POST ingredient request contained a sub-path");
         return bad_request_response()
     }
 
-    match authorization.authenticate_request(request) {
-        Ok(_) => (),
-        Err(_) => {
-            return unauthorized_response();
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
         }
-    }
+    };
 
-    let handle_request_result = block_on(post_ingredient::handle_post_ingredient_request(request, db_pool));
+    let handle_request_result = block_on(post_ingredient::handle_post_ingredient_request(request, db_pool, &identity));
     let post_ingredient_response_data = match handle_request_result {
         Ok(response_data) => response_data,
-        Err(err) => {
+        Err(err) if err.is::<serde_json::Error>() => {
             log::info!("Failed to handle post request: {}", err);
             return bad_request_response()
+        },
+        Err(err) => {
+            log::error!("Error creating ingredient - {}", err);
+            return internal_server_error_response()
         }
     };
 
+    log::info!("Ingredient {} created by {}", post_ingredient_response_data.id, identity);
     let ingredient_location = "/ingredient/".to_string() + post_ingredient_response_data.id.to_string().as_str();
     http::Response::builder()
         .status(http::status::StatusCode::CREATED)
         .header(http::header::LOCATION, ingredient_location)
         .body(vec![])
         .expect("error building response")
-}
\ No newline at end of file
+}
+
+fn handle_merge_request(request: &Request<Vec<u8>>, ingredient_id: &str, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    log::info!("Merging ingredient {} for {}", ingredient_id, identity);
+    block_on(merge_ingredient::handle_merge_request(request, ingredient_id, db_pool, &identity))
+}
@@ -0,0 +1,126 @@
+use http::{Request, Response};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::audit::{self, Action, AuditEntry};
+use crate::authorization::Identity;
+use crate::http::responses;
+use crate::recipe;
+
+#[derive(Debug, Deserialize)]
+struct MergeIngredientRequestData {
+    /// Ingredient that replaces the merged one
+    pub into: i64,
+}
+
+/// Replace every use of ingredient `ingredient_id` with another ingredient, then delete it. Recipes that used it get a
+/// new version
+pub async fn handle_merge_request(request: &Request<Vec<u8>>, ingredient_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let ingredient_id = match ingredient_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("Ingredient id must be an integer")
+    };
+
+    let merge_request: MergeIngredientRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse merge ingredient request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    if merge_request.into == ingredient_id {
+        return responses::bad_request_response_with_message("Cannot merge an ingredient into itself");
+    }
+
+    match merge_ingredient(ingredient_id, merge_request.into, db_pool, identity).await {
+        Ok(true) => {
+            log::info!("Merged ingredient {} into {}", ingredient_id, merge_request.into);
+            responses::empty_ok()
+        },
+        Ok(false) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error merging ingredient {} into {} - {}", ingredient_id, merge_request.into, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Returns whether both ingredients exist
+async fn merge_ingredient(ingredient_id: i64, into: i64, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<bool> {
+    let mut tx = db_pool.begin().await?;
+
+    let existing: Vec<(i64, String)> = sqlx::query_as("SELECT id, name FROM ingredients WHERE id = $1 OR id = $2 FOR UPDATE;")
+        .bind(ingredient_id)
+        .bind(into)
+        .fetch_all(&mut *tx).await?;
+    if existing.len() != 2 {
+        return Ok(false);
+    }
+    let merged_name = existing.iter()
+        .find(|(id, _)| *id == ingredient_id)
+        .map(|(_, name)| name.clone())
+        .unwrap_or_default();
+
+    // Read before repointing, which loses which recipes used the merged ingredient
+    let recipe_ids: Vec<i64> = sqlx::query_scalar("SELECT recipes.id
+            FROM recipes
+            JOIN recipe_ingredients ON recipe_ingredients.recipe_id = recipes.id
+            WHERE recipe_ingredients.ingredient_id = $1
+            ORDER BY recipes.id
+            FOR UPDATE OF recipes;")
+        .bind(ingredient_id)
+        .fetch_all(&mut *tx).await?;
+
+    // Recipes already using both keep their existing row for the target ingredient
+    sqlx::query("DELETE FROM recipe_ingredients
+            WHERE ingredient_id = $1
+            AND recipe_id IN (SELECT recipe_id FROM recipe_ingredients WHERE ingredient_id = $2);")
+        .bind(ingredient_id)
+        .bind(into)
+        .execute(&mut *tx).await?;
+    sqlx::query("UPDATE recipe_ingredients
+            SET ingredient_id = $1
+            WHERE ingredient_id = $2;")
+        .bind(into)
+        .bind(ingredient_id)
+        .execute(&mut *tx).await?;
+    // Likewise pantries already holding the target keep their item for it
+    sqlx::query("DELETE FROM user_pantry_items
+            WHERE ingredient_id = $1
+            AND user_id IN (SELECT user_id FROM user_pantry_items WHERE ingredient_id = $2);")
+        .bind(ingredient_id)
+        .bind(into)
+        .execute(&mut *tx).await?;
+    sqlx::query("UPDATE user_pantry_items
+            SET ingredient_id = $1
+            WHERE ingredient_id = $2;")
+        .bind(into)
+        .bind(ingredient_id)
+        .execute(&mut *tx).await?;
+    // Earlier merges into this ingredient now lead to the target too
+    sqlx::query("UPDATE ingredient_merges SET into_id = $1 WHERE into_id = $2;")
+        .bind(into)
+        .bind(ingredient_id)
+        .execute(&mut *tx).await?;
+    sqlx::query("INSERT INTO ingredient_merges (merged_id, into_id) VALUES ($1, $2);")
+        .bind(ingredient_id)
+        .bind(into)
+        .execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM ingredients WHERE id = $1;")
+        .bind(ingredient_id)
+        .execute(&mut *tx).await?;
+
+    recipe::bump_recipe_versions(&recipe_ids, identity, &mut tx).await?;
+
+    audit::record(&mut *tx, AuditEntry {
+        actor: identity,
+        resource: "ingredient",
+        resource_id: ingredient_id.to_string(),
+        action: Action::Delete,
+        before: Some(serde_json::json!({ "ingredient_id": ingredient_id, "name": merged_name })),
+        after: Some(serde_json::json!({ "merged_into": into })),
+    }).await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
@@ -1,6 +1,8 @@
 use http::Request;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use crate::audit::{self, Action, AuditEntry};
+use crate::authorization::Identity;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PostIngredientRequestData {
@@ -10,21 +12,38 @@ struct PostIngredientRequestData {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PostIngredientResponseData {
     pub id: i64,
+    pub name: String,
 }
 
-pub async fn handle_post_ingredient_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> anyhow::Result<PostIngredientResponseData> {
+pub async fn handle_post_ingredient_request(request: &Request<Vec<u8>>, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<PostIngredientResponseData> {
     let post_recipe_request: PostIngredientRequestData = serde_json::from_slice(request.body())?;
-    let created_id = insert_ingredient(post_recipe_request, db_pool).await?;
-    let response_data = PostIngredientResponseData { id: created_id };
+    let name = post_recipe_request.name.clone();
+    let created_id = insert_ingredient(post_recipe_request, db_pool, identity).await?;
+    let response_data = PostIngredientResponseData { id: created_id, name };
     Ok(response_data)
 }
 
-async fn insert_ingredient(ingredient: PostIngredientRequestData, db_pool: &PgPool) -> anyhow::Result<i64> {
+/// Inserts the ingredient and its audit log entry together, so an ingredient is never created unaudited
+async fn insert_ingredient(ingredient: PostIngredientRequestData, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<i64> {
+    let mut tx = db_pool.begin().await?;
     let inserted_recipe = sqlx::query!("INSERT INTO ingredients
             (name)
             VALUES ($1)
             RETURNING id;",
         ingredient.name)
-        .fetch_one(db_pool).await?;
+        .fetch_one(&mut *tx).await?;
+
+    audit::record(&mut *tx, AuditEntry {
+        actor: identity,
+        resource: "ingredient",
+        resource_id: inserted_recipe.id.to_string(),
+        action: Action::Create,
+        before: None,
+        after: Some(serde_json::json!({
+            "ingredient_id": inserted_recipe.id,
+            "name": ingredient.name,
+        })),
+    }).await?;
+    tx.commit().await?;
     Ok(inserted_recipe.id)
 }
\ No newline at end of file
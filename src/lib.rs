@@ -4,5 +4,9 @@ pub mod image;
 pub mod recipe;
 pub mod authorization;
 pub mod ingredient;
+pub mod user;
+pub mod rate_limit;
+pub mod audit;
+pub mod tag;
 
-pub use config::{ConfigFile, Config};
\ No newline at end of file
+pub use config::{ConfigFile, Config, RankingWeights, RateLimit, RateLimitConfig};
\ No newline at end of file
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::{RateLimit, RateLimitConfig};
+
+/// Number of clients tracked before buckets that have refilled are forgotten
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Budget {
+    Read,
+    Write,
+    FailedAuth,
+}
+
+/// Token bucket rate limiter, keyed by budget and client
+pub struct RateLimiter {
+    config: Mutex<RateLimitConfig>,
+    buckets: Mutex<HashMap<(Budget, String), Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * refill_per_second(limit)).min(limit.capacity as f64);
+        self.updated = now;
+    }
+
+    /// Time until a whole token is available
+    fn retry_after(&self, limit: RateLimit) -> Duration {
+        let rate = refill_per_second(limit);
+        if rate <= 0.0 {
+            return Duration::MAX;
+        }
+        Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / rate)
+    }
+}
+
+fn budget_limit(config: &RateLimitConfig, budget: Budget) -> Option<RateLimit> {
+    match budget {
+        Budget::Read => config.read,
+        Budget::Write => config.write,
+        Budget::FailedAuth => config.failed_auth,
+    }
+}
+
+fn refill_per_second(limit: RateLimit) -> f64 {
+    limit.per_minute as f64 / 60.0
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self { config: Mutex::new(config), buckets: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn set_config(&self, config: RateLimitConfig) {
+        *self.config.lock().expect("rate limit config lock poisoned") = config;
+    }
+
+    /// Take a token from `client`'s budget, or return how long to wait for one
+    pub fn try_acquire(&self, budget: Budget, client: &str) -> Result<(), Duration> {
+        self.update(budget, client, true)
+    }
+
+    /// Whether `client` has a token left in the budget, without taking it
+    pub fn check(&self, budget: Budget, client: &str) -> Result<(), Duration> {
+        self.update(budget, client, false)
+    }
+
+    fn update(&self, budget: Budget, client: &str, take: bool) -> Result<(), Duration> {
+        let limit = match self.limit(budget) {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("rate limit lock poisoned");
+        if buckets.len() > PRUNE_THRESHOLD {
+            let config = self.config.lock().expect("rate limit config lock poisoned").clone();
+            Self::prune(&mut buckets, &config, now);
+        }
+
+        let bucket = buckets.entry((budget, client.to_string()))
+            .or_insert(Bucket { tokens: limit.capacity as f64, updated: now });
+        bucket.refill(limit, now);
+
+        if bucket.tokens < 1.0 {
+            log::info!("Client {} exceeded its {:?} rate limit", client, budget);
+            return Err(bucket.retry_after(limit));
+        }
+        if take {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    fn limit(&self, budget: Budget) -> Option<RateLimit> {
+        budget_limit(&self.config.lock().expect("rate limit config lock poisoned"), budget)
+    }
+
+    /// Forget clients whose buckets have refilled, as they are indistinguishable from new clients
+    fn prune(buckets: &mut HashMap<(Budget, String), Bucket>, config: &RateLimitConfig, now: Instant) {
+        buckets.retain(|(budget, _), bucket| {
+            match budget_limit(config, *budget) {
+                Some(limit) => {
+                    bucket.refill(limit, now);
+                    bucket.tokens < limit.capacity as f64
+                },
+                None => false,
+            }
+        });
+    }
+}
@@ -1,25 +1,48 @@
+mod amount;
 mod get_recipe;
 mod get_all_recipes;
-mod database;
+pub(crate) mod database;
 mod post_recipe;
 mod put_recipe;
+mod delete_recipe;
+mod recipe_trash;
+mod recipe_ingredients;
+mod recipe_tags;
+mod recipe_feedback;
+mod revision;
+mod ingredient_filter;
+mod tag_filter;
+mod ranking;
+mod scaling;
+pub(crate) mod match_recipes;
+
+pub use amount::migrate_amounts;
+pub(crate) use recipe_tags::{bump_recipe_versions, tagged_recipe_ids};
 
 use futures::executor::block_on;
-use crate::http::responses::{bad_request_response, method_not_allowed_response, unauthorized_response};
+use crate::http::responses::{bad_request_response, bad_request_response_with_message, method_not_allowed_response};
 use crate::recipe::get_recipe::get_recipe_with_id;
+use crate::recipe::put_recipe::UpdateSource;
 use http::{Method, Request, Response, Uri};
-use sqlx::PgPool;
-use crate::authorization::Authorization;
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::audit::{self, Action, AuditEntry};
+use crate::authorization::{Authorization, Identity};
+use crate::Config;
 
 pub fn can_handle_request(request: &Request<Vec<u8>>) -> bool {
     request.uri().path().starts_with("/recipe/") || request.uri().path() == "/recipe"
 }
 
-pub fn handle_request(request: Request<Vec<u8>>, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
+pub fn handle_request(request: Request<Vec<u8>>, config: &Config, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
     match *request.method() {
-        Method::GET => handle_get_request(&request, db_pool),
+        Method::GET => handle_get_request(&request, config, db_pool, auth_handler),
         Method::POST => handle_post_request(&request, db_pool, auth_handler),
-        Method::PUT => handle_put_request(&request, db_pool, auth_handler),
+        Method::PUT | Method::PATCH => match chunk_url(request.uri()).as_slice() {
+            ["recipe", recipe_id] => handle_update_request(&request, recipe_id, UpdateSource::Body, db_pool, auth_handler),
+            ["recipe", _, "rating" | "favorite"] if request.method() == Method::PUT => handle_feedback_request(&request, db_pool, auth_handler),
+            _ => bad_request_response_with_message("Request uri should contain 2 chunks"),
+        },
+        Method::DELETE => handle_delete_request(&request, db_pool, auth_handler),
         _ => method_not_allowed_response()
     }
 }
@@ -28,31 +51,69 @@ pub fn chunk_url(uri: &Uri) -> Vec<&str> {
     uri.path()[1..].split("/").filter(|chunk| !chunk.is_empty()).collect::<Vec<&str>>()
 }
 
-fn handle_get_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
+fn handle_get_request(request: &Request<Vec<u8>>, config: &Config, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
     let url_chunks = chunk_url(request.uri());
+    if url_chunks.as_slice() == ["recipe", "trash"] {
+        return handle_get_trash_request(request, db_pool, authorization);
+    }
+    if url_chunks.as_slice() == ["recipe", "match"] {
+        return block_on(match_recipes::get_recipe_matches(request, db_pool));
+    }
 
-    match url_chunks.len() {
-        1 => block_on(get_all_recipes::get_all_recipes(db_pool)),
-        2 => block_on(get_recipe_with_id(db_pool, url_chunks.last().unwrap())),
+    match url_chunks.as_slice() {
+        [_] => block_on(get_all_recipes::get_all_recipes(request, db_pool, &config.ranking)),
+        [_, recipe_id] => block_on(get_recipe_with_id(request, db_pool, recipe_id)),
+        [_, recipe_id, "revision"] => match recipe_id.parse::<i64>() {
+            Ok(recipe_id) => block_on(revision::get_revisions(db_pool, recipe_id)),
+            Err(_) => bad_request_response_with_message("Recipe id must be an integer"),
+        },
+        [_, recipe_id, "revision", revision] => match (recipe_id.parse::<i64>(), revision.parse::<i64>()) {
+            (Ok(recipe_id), Ok(revision)) => block_on(revision::get_revision(db_pool, recipe_id, revision)),
+            _ => bad_request_response_with_message("Recipe id and revision must be integers"),
+        },
+        [_, recipe_id, "revision", from, "diff", to] => match (recipe_id.parse::<i64>(), from.parse::<i64>(), to.parse::<i64>()) {
+            (Ok(recipe_id), Ok(from), Ok(to)) => block_on(revision::get_revision_diff(db_pool, recipe_id, from, to)),
+            _ => bad_request_response_with_message("Recipe id and revisions must be integers"),
+        },
         _ => bad_request_response()
     }
 }
 
+fn handle_get_trash_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    block_on(recipe_trash::get_trash(db_pool, &identity))
+}
+
 fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
     let url_chunks = chunk_url(request.uri());
+    if let ["recipe", recipe_id, "restore"] = url_chunks.as_slice() {
+        return handle_restore_request(request, recipe_id, db_pool, authorization);
+    }
+    if let ["recipe", recipe_id, "revision", revision, "restore"] = url_chunks.as_slice() {
+        return match revision.parse::<i64>() {
+            Ok(revision) => handle_update_request(request, recipe_id, UpdateSource::Revision(revision), db_pool, authorization),
+            Err(_) => bad_request_response_with_message("Revision must be an integer"),
+        };
+    }
     if url_chunks.len() != 1 {
         log::info!("Bad request - POST recipe request contained a sub-path");
         return bad_request_response()
     }
 
-    match authorization.authenticate_request(request) {
-        Ok(_) => (),
-        Err(_) => {
-            return unauthorized_response();
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
         }
-    }
+    };
 
-    let handle_request_result = block_on(post_recipe::handle_post_request(request, db_pool));
+    let handle_request_result = block_on(post_recipe::handle_post_request(request, db_pool, &identity));
     let post_recipe_response_data = match handle_request_result {
         Ok(response_data) => response_data,
         Err(err) => {
@@ -61,6 +122,7 @@ fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorizati
         }
     };
 
+    log::info!("Recipe {} created by {}", post_recipe_response_data.recipe_id, identity);
     let recipe_location = "/recipe/".to_string() + post_recipe_response_data.recipe_id.to_string().as_str();
     http::Response::builder()
         .status(http::status::StatusCode::CREATED)
@@ -69,13 +131,99 @@ fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorizati
         .expect("error building response")
 }
 
-fn handle_put_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
-    match authorization.authenticate_request(request) {
-        Ok(_) => (),
-        Err(_) => {
-            return unauthorized_response();
+fn handle_update_request(request: &Request<Vec<u8>>, recipe_id: &str, source: UpdateSource, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let recipe_id = match recipe_id.parse::<i64>() {
+        Ok(recipe_id) => recipe_id,
+        Err(_) => return bad_request_response_with_message("Recipe id must be an integer"),
+    };
+
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
         }
+    };
+
+    log::info!("Updating recipe '{}' for {}", request.uri(), identity);
+    block_on(put_recipe::handle_update_request(request, recipe_id, source, db_pool, &identity))
+}
+
+fn handle_delete_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    if let ["recipe", _, "rating" | "favorite"] = chunk_url(request.uri()).as_slice() {
+        return handle_feedback_request(request, db_pool, authorization);
     }
 
-    block_on(put_recipe::handle_put_request(request, db_pool))
+    let recipe_id = match chunk_url(request.uri()).as_slice() {
+        ["recipe", recipe_id] => match recipe_id.parse::<i64>() {
+            Ok(recipe_id) => recipe_id,
+            Err(_) => return bad_request_response_with_message("Recipe id must be an integer"),
+        },
+        _ => return bad_request_response(),
+    };
+
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    block_on(delete_recipe::handle_delete_request(request, recipe_id, db_pool, &identity))
+}
+
+/// Setting or removing the caller's rating or favorite of a recipe
+fn handle_feedback_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let url_chunks = chunk_url(request.uri());
+    let (recipe_id, feedback) = match url_chunks.as_slice() {
+        ["recipe", recipe_id, feedback] => match recipe_id.parse::<i64>() {
+            Ok(recipe_id) => (recipe_id, *feedback),
+            Err(_) => return bad_request_response_with_message("Recipe id must be an integer"),
+        },
+        _ => return bad_request_response(),
+    };
+
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    match (request.method(), feedback) {
+        (&Method::PUT, "rating") => block_on(recipe_feedback::handle_put_rating_request(request, recipe_id, db_pool, &identity)),
+        (&Method::DELETE, "rating") => block_on(recipe_feedback::handle_delete_rating_request(recipe_id, db_pool, &identity)),
+        (&Method::PUT, "favorite") => block_on(recipe_feedback::handle_put_favorite_request(recipe_id, db_pool, &identity)),
+        (&Method::DELETE, "favorite") => block_on(recipe_feedback::handle_delete_favorite_request(recipe_id, db_pool, &identity)),
+        _ => method_not_allowed_response(),
+    }
+}
+
+fn handle_restore_request(request: &Request<Vec<u8>>, recipe_id: &str, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let recipe_id = match recipe_id.parse::<i64>() {
+        Ok(recipe_id) => recipe_id,
+        Err(_) => return bad_request_response_with_message("Recipe id must be an integer"),
+    };
+
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    block_on(recipe_trash::handle_restore_request(recipe_id, db_pool, &identity))
+}
+
+/// Record a change to the recipe in the audit log within the transaction making it, so the entry is written exactly
+/// when the change is. `before` is the recipe's snapshot taken in the same transaction before the change
+async fn record_recipe_change(tx: &mut Transaction<'_, Postgres>, identity: &Identity, recipe_id: i64, action: Action, before: Option<serde_json::Value>) -> anyhow::Result<()> {
+    let after = get_recipe::recipe_snapshot(tx, recipe_id).await?;
+    audit::record(&mut **tx, AuditEntry {
+        actor: identity,
+        resource: "recipe",
+        resource_id: recipe_id.to_string(),
+        action,
+        before,
+        after,
+    }).await
 }
\ No newline at end of file
@@ -0,0 +1,497 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+const MAX_NOTE_LENGTH: usize = 200;
+/// How close a quantity must be to a common fraction to be shown as one
+const FRACTION_TOLERANCE: f64 = 0.01;
+/// Fractions quantities are shown as, smallest first
+pub(super) const DISPLAY_FRACTIONS: [(f64, &str); 6] = [(0.125, "1/8"), (0.25, "1/4"), (1.0 / 3.0, "1/3"), (0.5, "1/2"), (2.0 / 3.0, "2/3"), (0.75, "3/4")];
+const UNICODE_FRACTIONS: [(char, f64); 7] = [('¼', 0.25), ('½', 0.5), ('¾', 0.75), ('⅓', 1.0 / 3.0), ('⅔', 2.0 / 3.0), ('⅛', 0.125), ('⅜', 0.375)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Gram,
+    Kilogram,
+    Millilitre,
+    Litre,
+    Teaspoon,
+    Tablespoon,
+    Cup,
+    Ounce,
+    Pound,
+    Pinch,
+    Clove,
+    Can,
+    Slice,
+    Handful,
+    Bunch,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 15] = [
+        Unit::Gram,
+        Unit::Kilogram,
+        Unit::Millilitre,
+        Unit::Litre,
+        Unit::Teaspoon,
+        Unit::Tablespoon,
+        Unit::Cup,
+        Unit::Ounce,
+        Unit::Pound,
+        Unit::Pinch,
+        Unit::Clove,
+        Unit::Can,
+        Unit::Slice,
+        Unit::Handful,
+        Unit::Bunch,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Gram => "g",
+            Unit::Kilogram => "kg",
+            Unit::Millilitre => "ml",
+            Unit::Litre => "l",
+            Unit::Teaspoon => "tsp",
+            Unit::Tablespoon => "tbsp",
+            Unit::Cup => "cup",
+            Unit::Ounce => "oz",
+            Unit::Pound => "lb",
+            Unit::Pinch => "pinch",
+            Unit::Clove => "clove",
+            Unit::Can => "can",
+            Unit::Slice => "slice",
+            Unit::Handful => "handful",
+            Unit::Bunch => "bunch",
+        }
+    }
+
+    /// The unit as written after `quantity`, plural where it's a word
+    fn display_for(&self, quantity: f64) -> String {
+        let plural = match self {
+            Unit::Cup | Unit::Clove | Unit::Can | Unit::Slice | Unit::Handful => "s",
+            Unit::Pinch | Unit::Bunch => "es",
+            _ => "",
+        };
+        match quantity > 1.0 {
+            true => format!("{}{}", self.as_str(), plural),
+            false => self.as_str().to_string(),
+        }
+    }
+
+    /// Metric quantities are shown as decimals rather than fractions
+    fn is_metric(&self) -> bool {
+        matches!(self, Unit::Gram | Unit::Kilogram | Unit::Millilitre | Unit::Litre)
+    }
+
+    /// Other ways of writing the unit, accepted when parsing, in lower case
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Unit::Gram => &["gram", "grams", "gr"],
+            Unit::Kilogram => &["kilogram", "kilograms", "kilo", "kilos", "kgs"],
+            Unit::Millilitre => &["millilitre", "millilitres", "milliliter", "milliliters", "mls"],
+            Unit::Litre => &["litre", "litres", "liter", "liters"],
+            Unit::Teaspoon => &["teaspoon", "teaspoons", "tsps"],
+            Unit::Tablespoon => &["tablespoon", "tablespoons", "tbs", "tbsps"],
+            Unit::Cup => &["cups"],
+            Unit::Ounce => &["ounce", "ounces"],
+            Unit::Pound => &["pound", "pounds", "lbs"],
+            Unit::Pinch => &["pinches"],
+            Unit::Clove => &["cloves"],
+            Unit::Can => &["cans", "tin", "tins"],
+            Unit::Slice => &["slices"],
+            Unit::Handful => &["handfuls"],
+            Unit::Bunch => &["bunches"],
+        }
+    }
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().trim_end_matches('.').to_lowercase();
+        match Unit::ALL.into_iter().find(|unit| unit.as_str() == value || unit.aliases().contains(&value.as_str())) {
+            Some(unit) => Ok(unit),
+            None => bail!("Unknown unit '{}'", value),
+        }
+    }
+}
+
+/// How much of an ingredient a recipe needs - a quantity, optionally in a unit, and a free text note for anything
+/// else ("heaped", "to taste"). At least one of quantity and note is given.
+///
+/// Read either as these fields or, for older clients, as a free text `amount` to parse. Written with the display
+/// form as `amount` alongside the fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "AmountData", into = "AmountData")]
+pub struct Amount {
+    pub quantity: Option<f64>,
+    pub unit: Option<Unit>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AmountData {
+    quantity: Option<f64>,
+    unit: Option<String>,
+    note: Option<String>,
+    amount: Option<String>,
+}
+
+impl TryFrom<AmountData> for Amount {
+    type Error = anyhow::Error;
+
+    fn try_from(value: AmountData) -> Result<Self, Self::Error> {
+        // The structured fields win, so an amount read from the API can be sent back with them changed
+        if value.quantity.is_none() && value.unit.is_none() && value.note.is_none() {
+            return match value.amount {
+                Some(amount) => Amount::parse(&amount),
+                None => bail!("Give an amount, or a quantity, unit or note"),
+            };
+        }
+
+        let unit = value.unit.map(|unit| Unit::from_str(&unit)).transpose()?;
+        Amount::new(value.quantity, unit, value.note)
+    }
+}
+
+impl From<Amount> for AmountData {
+    fn from(value: Amount) -> Self {
+        AmountData {
+            amount: Some(value.to_string()),
+            quantity: value.quantity,
+            unit: value.unit.map(|unit| unit.as_str().to_string()),
+            note: value.note,
+        }
+    }
+}
+
+impl Amount {
+    pub fn new(quantity: Option<f64>, unit: Option<Unit>, note: Option<String>) -> anyhow::Result<Self> {
+        let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+        if quantity.is_some_and(|quantity| !quantity.is_finite() || quantity <= 0.0) {
+            bail!("Quantity must be a positive number");
+        }
+        if unit.is_some() && quantity.is_none() {
+            bail!("A unit needs a quantity");
+        }
+        if quantity.is_none() && note.is_none() {
+            bail!("Give a quantity or a note");
+        }
+        if note.as_ref().is_some_and(|note| note.chars().count() > MAX_NOTE_LENGTH) {
+            bail!("Note must be at most {} characters", MAX_NOTE_LENGTH);
+        }
+        Ok(Amount { quantity, unit, note })
+    }
+
+    /// Read free text such as "2 tbsp", "1 1/2 cups sifted", "200g" or "½ tsp". Text that doesn't start with a
+    /// quantity is kept whole as the note
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let text = text.trim();
+        match parse_structured(text) {
+            Some((quantity, unit, note)) => Amount::new(Some(quantity), unit, note),
+            None if text.is_empty() => Err(anyhow!("Amount must not be empty")),
+            None => Amount::new(None, None, Some(text.to_string())),
+        }
+    }
+
+    /// An amount as stored in `recipe_ingredients`. Rows written before amounts were structured only have the free
+    /// text `amount`, which is parsed
+    pub fn from_columns(quantity: Option<f64>, unit: Option<String>, note: Option<String>, amount: &str) -> Self {
+        if quantity.is_none() && unit.is_none() && note.is_none() {
+            return Amount::parse(amount).unwrap_or(Amount { quantity: None, unit: None, note: Some(amount.to_string()) });
+        }
+        Amount { quantity, unit: unit.and_then(|unit| Unit::from_str(&unit).ok()), note }
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if let Some(quantity) = self.quantity {
+            parts.push(format_quantity(quantity, self.unit.is_some_and(|unit| unit.is_metric())));
+        }
+        if let (Some(quantity), Some(unit)) = (self.quantity, self.unit) {
+            parts.push(unit.display_for(quantity));
+        }
+        if let Some(note) = &self.note {
+            parts.push(note.clone());
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// Parse the free text amounts of rows written before amounts were structured, returning how many were migrated.
+/// Text that can't be read as a quantity becomes the note, and empty amounts are left alone
+pub async fn migrate_amounts(db_pool: &PgPool) -> anyhow::Result<usize> {
+    let mut tx = db_pool.begin().await?;
+    let rows: Vec<(i64, String)> = sqlx::query_as("SELECT id, amount
+            FROM recipe_ingredients
+            WHERE quantity IS NULL AND unit IS NULL AND note IS NULL
+            FOR UPDATE;")
+        .fetch_all(&mut *tx).await?;
+
+    let mut migrated = 0;
+    for (id, text) in rows {
+        let amount = match Amount::parse(&text) {
+            Ok(amount) => amount,
+            Err(err) => {
+                log::warn!("Leaving amount '{}' of recipe ingredient {} unmigrated - {}", text, id, err);
+                continue;
+            }
+        };
+        sqlx::query("UPDATE recipe_ingredients
+                SET amount = $1, quantity = $2, unit = $3, note = $4
+                WHERE id = $5;")
+            .bind(amount.to_string())
+            .bind(amount.quantity)
+            .bind(amount.unit.map(|unit| unit.as_str()))
+            .bind(&amount.note)
+            .bind(id)
+            .execute(&mut *tx).await?;
+        migrated += 1;
+    }
+
+    tx.commit().await?;
+    Ok(migrated)
+}
+
+/// Quantity, unit and note of text starting with a quantity
+fn parse_structured(text: &str) -> Option<(f64, Option<Unit>, Option<String>)> {
+    let mut words = text.split_whitespace().peekable();
+    let (mut quantity, glued) = split_quantity(words.next()?)?;
+
+    // "1 1/2" or "1 ½"
+    if glued.is_empty() && quantity.fract() == 0.0 {
+        if let Some(fraction) = words.peek().and_then(|word| parse_fraction(word)) {
+            quantity += fraction;
+            words.next();
+        }
+    }
+
+    // "200g" has its unit glued on, anything else glued on isn't understood
+    let unit = match glued.is_empty() {
+        false => Some(Unit::from_str(glued).ok()?),
+        true => match words.peek().and_then(|word| Unit::from_str(word).ok()) {
+            Some(unit) => {
+                words.next();
+                Some(unit)
+            },
+            None => None,
+        },
+    };
+    if unit.is_some() && words.peek().is_some_and(|word| word.eq_ignore_ascii_case("of")) {
+        words.next();
+    }
+
+    let note = words.collect::<Vec<&str>>().join(" ");
+    Some((quantity, unit, Some(note).filter(|note| !note.is_empty())))
+}
+
+/// A leading quantity - "2", "1.5", "1/2", "½" or "1½" - and whatever follows it in the same word
+fn split_quantity(word: &str) -> Option<(f64, &str)> {
+    let number_end = word.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '/')).unwrap_or(word.len());
+    let (number, rest) = word.split_at(number_end);
+
+    let mut quantity = match number.is_empty() {
+        true => 0.0,
+        false => parse_fraction(number).or_else(|| number.parse::<f64>().ok())?,
+    };
+    let mut rest = rest;
+    if let Some((fraction, length)) = rest.chars().next().and_then(|c| unicode_fraction(c).map(|fraction| (fraction, c.len_utf8()))) {
+        quantity += fraction;
+        rest = &rest[length..];
+    }
+
+    match quantity > 0.0 && quantity.is_finite() {
+        true => Some((quantity, rest)),
+        false => None,
+    }
+}
+
+/// "1/2" or "½"
+fn parse_fraction(word: &str) -> Option<f64> {
+    let mut chars = word.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return unicode_fraction(c);
+    }
+
+    let (numerator, denominator) = word.split_once('/')?;
+    let numerator = numerator.parse::<u32>().ok()?;
+    let denominator = denominator.parse::<u32>().ok().filter(|denominator| *denominator > 0)?;
+    Some(numerator as f64 / denominator as f64)
+}
+
+fn unicode_fraction(c: char) -> Option<f64> {
+    UNICODE_FRACTIONS.iter().find(|(fraction, _)| *fraction == c).map(|(_, value)| *value)
+}
+
+/// Whole numbers as they are, common fractions as fractions unless `decimal`, anything else to two decimal places.
+/// Quantities too small for two decimal places keep one significant figure rather than showing as 0
+fn format_quantity(quantity: f64, decimal: bool) -> String {
+    if quantity > 0.0 && quantity < FRACTION_TOLERANCE {
+        let decimals = (-quantity.log10()).floor() as usize + 1;
+        return format!("{:.*}", decimals, quantity).trim_end_matches('0').trim_end_matches('.').to_string();
+    }
+
+    let whole = quantity.trunc();
+    let fraction = quantity - whole;
+    if fraction < FRACTION_TOLERANCE {
+        return format!("{}", whole);
+    }
+    if 1.0 - fraction < FRACTION_TOLERANCE {
+        return format!("{}", whole + 1.0);
+    }
+
+    match DISPLAY_FRACTIONS.iter().find(|(value, _)| !decimal && (fraction - value).abs() < FRACTION_TOLERANCE) {
+        Some((_, display)) if whole == 0.0 => display.to_string(),
+        Some((_, display)) => format!("{} {}", whole, display),
+        None => format!("{:.2}", quantity).trim_end_matches('0').trim_end_matches('.').to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(quantity: Option<f64>, unit: Option<Unit>, note: Option<&str>) -> Amount {
+        Amount { quantity, unit, note: note.map(str::to_string) }
+    }
+
+    #[test]
+    fn parses_mixed_numbers_with_a_unit_and_note() {
+        assert_eq!(Amount::parse("1 1/2 cups sifted").unwrap(), amount(Some(1.5), Some(Unit::Cup), Some("sifted")));
+    }
+
+    #[test]
+    fn parses_units_glued_to_the_quantity() {
+        assert_eq!(Amount::parse("200g").unwrap(), amount(Some(200.0), Some(Unit::Gram), None));
+        assert_eq!(Amount::parse("1.5kg").unwrap(), amount(Some(1.5), Some(Unit::Kilogram), None));
+    }
+
+    #[test]
+    fn parses_unicode_fractions() {
+        assert_eq!(Amount::parse("½ tsp").unwrap(), amount(Some(0.5), Some(Unit::Teaspoon), None));
+        assert_eq!(Amount::parse("1½tbsp").unwrap(), amount(Some(1.5), Some(Unit::Tablespoon), None));
+        assert_eq!(Amount::parse("1 ¼ cups").unwrap(), amount(Some(1.25), Some(Unit::Cup), None));
+    }
+
+    #[test]
+    fn parses_unit_aliases_ignoring_case() {
+        assert_eq!(Amount::parse("2 Tablespoons").unwrap(), amount(Some(2.0), Some(Unit::Tablespoon), None));
+        assert_eq!(Amount::parse("3 tsp.").unwrap(), amount(Some(3.0), Some(Unit::Teaspoon), None));
+    }
+
+    #[test]
+    fn skips_of_after_a_unit() {
+        assert_eq!(Amount::parse("2 cups of flour").unwrap(), amount(Some(2.0), Some(Unit::Cup), Some("flour")));
+    }
+
+    #[test]
+    fn keeps_a_quantity_without_a_unit() {
+        assert_eq!(Amount::parse("2 eggs").unwrap(), amount(Some(2.0), None, Some("eggs")));
+    }
+
+    #[test]
+    fn keeps_ranges_and_words_whole_as_the_note() {
+        assert_eq!(Amount::parse("1-2 cloves").unwrap(), amount(None, None, Some("1-2 cloves")));
+        assert_eq!(Amount::parse("a handful").unwrap(), amount(None, None, Some("a handful")));
+    }
+
+    #[test]
+    fn rejects_empty_text() {
+        assert!(Amount::parse("").is_err());
+        assert!(Amount::parse("   ").is_err());
+    }
+
+    #[test]
+    fn new_checks_quantity_unit_and_note() {
+        assert!(Amount::new(Some(0.0), None, None).is_err());
+        assert!(Amount::new(Some(-1.0), None, None).is_err());
+        assert!(Amount::new(Some(f64::NAN), None, None).is_err());
+        assert!(Amount::new(None, Some(Unit::Gram), Some("some".to_string())).is_err());
+        assert!(Amount::new(None, None, Some("  ".to_string())).is_err());
+        assert!(Amount::new(None, None, Some("x".repeat(MAX_NOTE_LENGTH + 1))).is_err());
+        assert_eq!(Amount::new(None, None, Some(" to taste ".to_string())).unwrap(), amount(None, None, Some("to taste")));
+    }
+
+    #[test]
+    fn split_quantity_returns_what_follows_the_number() {
+        assert_eq!(split_quantity("200g"), Some((200.0, "g")));
+        assert_eq!(split_quantity("1/2"), Some((0.5, "")));
+        assert_eq!(split_quantity("1½"), Some((1.5, "")));
+        assert_eq!(split_quantity("1-2"), Some((1.0, "-2")));
+        assert_eq!(split_quantity("a"), None);
+        assert_eq!(split_quantity("0"), None);
+    }
+
+    #[test]
+    fn parse_fraction_reads_slashes_and_unicode() {
+        assert_eq!(parse_fraction("3/4"), Some(0.75));
+        assert_eq!(parse_fraction("¾"), Some(0.75));
+        assert_eq!(parse_fraction("1/0"), None);
+        assert_eq!(parse_fraction("1.5"), None);
+    }
+
+    #[test]
+    fn formats_quantities_as_fractions_or_decimals() {
+        assert_eq!(format_quantity(2.0, false), "2");
+        assert_eq!(format_quantity(0.5, false), "1/2");
+        assert_eq!(format_quantity(1.0 / 3.0, false), "1/3");
+        assert_eq!(format_quantity(2.75, false), "2 3/4");
+        assert_eq!(format_quantity(0.125, false), "1/8");
+        assert_eq!(format_quantity(2.5, true), "2.5");
+        assert_eq!(format_quantity(1.2345, false), "1.23");
+        assert_eq!(format_quantity(0.999, false), "1");
+    }
+
+    #[test]
+    fn formats_tiny_quantities_to_one_significant_figure() {
+        assert_eq!(format_quantity(0.004, false), "0.004");
+        assert_eq!(format_quantity(0.0005, true), "0.0005");
+        assert_eq!(format_quantity(0.0099, false), "0.01");
+        assert_eq!(format_quantity(0.0, false), "0");
+    }
+
+    #[test]
+    fn displays_plural_word_units() {
+        assert_eq!(amount(Some(2.0), Some(Unit::Cup), None).to_string(), "2 cups");
+        assert_eq!(amount(Some(1.0), Some(Unit::Cup), None).to_string(), "1 cup");
+        assert_eq!(amount(Some(3.0), Some(Unit::Pinch), None).to_string(), "3 pinches");
+        assert_eq!(amount(Some(2.0), Some(Unit::Tablespoon), None).to_string(), "2 tbsp");
+        assert_eq!(amount(Some(1.5), Some(Unit::Clove), Some("crushed")).to_string(), "1 1/2 cloves crushed");
+        assert_eq!(amount(Some(2.5), Some(Unit::Gram), None).to_string(), "2.5 g");
+    }
+
+    #[test]
+    fn from_columns_parses_unmigrated_text() {
+        assert_eq!(Amount::from_columns(None, None, None, "2 tbsp"), amount(Some(2.0), Some(Unit::Tablespoon), None));
+        assert_eq!(Amount::from_columns(Some(3.0), Some("g".to_string()), None, "ignored"), amount(Some(3.0), Some(Unit::Gram), None));
+    }
+
+    #[test]
+    fn reads_structured_fields_before_the_amount_text() {
+        let parsed: Amount = serde_json::from_str(r#"{ "quantity": 2, "unit": "cup", "amount": "1 tsp" }"#).unwrap();
+        assert_eq!(parsed, amount(Some(2.0), Some(Unit::Cup), None));
+        let parsed: Amount = serde_json::from_str(r#"{ "amount": "1 tsp" }"#).unwrap();
+        assert_eq!(parsed, amount(Some(1.0), Some(Unit::Teaspoon), None));
+        assert!(serde_json::from_str::<Amount>("{}").is_err());
+    }
+
+    #[test]
+    fn writes_the_display_form_as_amount() {
+        let written = serde_json::to_value(amount(Some(0.5), Some(Unit::Teaspoon), Some("heaped"))).unwrap();
+        assert_eq!(written["amount"], "1/2 tsp heaped");
+        assert_eq!(written["unit"], "tsp");
+    }
+}
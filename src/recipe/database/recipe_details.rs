@@ -1,6 +1,6 @@
 use anyhow::bail;
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::PgExecutor;
 
 #[derive(Debug, Serialize)]
 pub struct RecipeDetails {
@@ -25,8 +25,8 @@ struct RecipeDetailsViewItem {
 }
 
 impl RecipeDetails {
-    pub async fn fetch_from_recipe_id(db_pool: &PgPool, recipe_id: i64) -> anyhow::Result<Option<Self>> {
-        let recipe_details = sqlx::query_as!(RecipeDetailsViewItem, "SELECT * FROM recipe_details WHERE recipe_id = $1;", recipe_id).fetch_optional(db_pool).await?;
+    pub async fn fetch_from_recipe_id<'e>(executor: impl PgExecutor<'e>, recipe_id: i64) -> anyhow::Result<Option<Self>> {
+        let recipe_details = sqlx::query_as!(RecipeDetailsViewItem, "SELECT * FROM recipe_details WHERE recipe_id = $1;", recipe_id).fetch_optional(executor).await?;
         let recipe_details: Option<RecipeDetails> = match recipe_details {
             Some(recipe) => Some(recipe.try_into()?),
             None => None
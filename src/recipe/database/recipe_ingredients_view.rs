@@ -1,6 +1,7 @@
 use anyhow::bail;
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::PgExecutor;
+use crate::recipe::amount::Amount;
 
 #[derive(Debug, Serialize)]
 pub struct RecipeIngredientsView {
@@ -8,16 +9,19 @@ pub struct RecipeIngredientsView {
     pub ingredient_id: i64,
     pub recipe_name: String,
     pub ingredient_name: String,
-    pub amount: String
+    pub amount: Amount
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 struct RecipeIngredientsViewItem {
     pub recipe_id: Option<i64>,
     pub ingredient_id: Option<i64>,
     pub recipe_name: Option<String>,
     pub ingredient_name: Option<String>,
-    pub amount: Option<String>
+    pub amount: Option<String>,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub note: Option<String>,
 }
 
 impl TryFrom<RecipeIngredientsViewItem> for RecipeIngredientsView {
@@ -40,7 +44,7 @@ impl TryFrom<RecipeIngredientsViewItem> for RecipeIngredientsView {
             None => bail!("RecipeIngredientsViewItem missing ingredient_name"),
         };
         let amount = match value.amount {
-            Some(amount) => amount,
+            Some(amount) => Amount::from_columns(value.quantity, value.unit, value.note, &amount),
             None => bail!("RecipeIngredientsViewItem missing amount"),
         };
 
@@ -55,9 +59,11 @@ impl TryFrom<RecipeIngredientsViewItem> for RecipeIngredientsView {
 }
 
 impl RecipeIngredientsView {
-    pub async fn fetch_from_recipe_id(db_pool: &PgPool, recipe_id: i64) -> anyhow::Result<Option<Vec<Self>>> {
-        let recipe_ingredients = sqlx::query_as!(RecipeIngredientsViewItem, "SELECT * FROM recipe_ingredients_list WHERE recipe_id = $1;", recipe_id).fetch_all(db_pool).await?;
-        if recipe_ingredients.len() == 0 {
+    pub async fn fetch_from_recipe_id<'e>(executor: impl PgExecutor<'e>, recipe_id: i64) -> anyhow::Result<Option<Vec<Self>>> {
+        let recipe_ingredients: Vec<RecipeIngredientsViewItem> = sqlx::query_as("SELECT * FROM recipe_ingredients_list WHERE recipe_id = $1;")
+            .bind(recipe_id)
+            .fetch_all(executor).await?;
+        if recipe_ingredients.is_empty() {
             return Ok(None);
         }
 
@@ -1,8 +1,8 @@
 use anyhow::bail;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct RecipeOverview {
     pub recipe_id: i64,
     pub recipe_name: String,
@@ -10,9 +10,10 @@ pub struct RecipeOverview {
     pub image_uri: Option<String>,
     pub user_id: i64,
     pub user_name: String,
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, sqlx::FromRow)]
 struct RecipeOverviewViewItem {
     pub recipe_id: Option<i64>,
     pub recipe_name: Option<String>,
@@ -20,32 +21,23 @@ struct RecipeOverviewViewItem {
     pub image_uri: Option<String>,
     pub user_id: Option<i64>,
     pub user_name: Option<String>,
+    pub tags: Option<Vec<String>>,
 }
 
 impl RecipeOverview {
-    pub async fn get_all_recipe_overviews(db_pool: &PgPool) -> anyhow::Result<Vec<RecipeOverview>> {
-        let recipes = sqlx::query_as!(RecipeOverviewViewItem, "SELECT * FROM recipe_overviews;").fetch_all(db_pool).await?;
+    pub async fn get_recipe_overviews_for_user(user_id: i64, db_pool: &PgPool) -> anyhow::Result<Vec<RecipeOverview>> {
+        let recipes: Vec<RecipeOverviewViewItem> = sqlx::query_as("SELECT * FROM recipe_overviews WHERE user_id = $1;")
+            .bind(user_id)
+            .fetch_all(db_pool).await?;
         let mut recipe_vec = vec![];
         for recipe in recipes {
             match recipe.try_into() {
                 Ok(recipe) => recipe_vec.push(recipe),
-                Err(err) => println!("{}", err)
+                Err(err) => log::error!("Error reading overview of a recipe by user {} - {}", user_id, err)
             }
         }
         Ok(recipe_vec)
     }
-
-    pub async fn get_recipe_overview(recipe_id: i64, db_pool: &PgPool) -> anyhow::Result<Option<RecipeOverview>> {
-        let recipe = sqlx::query_as!(RecipeOverviewViewItem,
-            "SELECT * FROM recipe_overviews WHERE recipe_id = $1;", recipe_id).fetch_optional(db_pool).await?;
-        match recipe {
-            Some(recipe) => {
-                let recipe = recipe.try_into()?;
-                Ok(Some(recipe))
-            },
-            None => Ok(None)
-        }
-    }
 }
 
 impl TryFrom<RecipeOverviewViewItem> for RecipeOverview {
@@ -73,6 +65,7 @@ impl TryFrom<RecipeOverviewViewItem> for RecipeOverview {
             Some(name) => name,
             None => bail!("RecipeOverviewViewItem missing user_name"),
         };
+        let tags = value.tags.unwrap_or_default();
 
         Ok(RecipeOverview {
             recipe_id,
@@ -80,7 +73,8 @@ impl TryFrom<RecipeOverviewViewItem> for RecipeOverview {
             brief_description,
             image_uri,
             user_id,
-            user_name
+            user_name,
+            tags
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,99 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::audit::Action;
+use crate::authorization::Identity;
+use crate::http::QueryParams;
+use http::{Request, Response};
+use crate::http::responses;
+use crate::recipe::{get_recipe, record_recipe_change};
+
+/// Owner of a recipe and whether it is in the trash
+#[derive(Debug, sqlx::FromRow)]
+pub struct RecipeState {
+    pub user_id: i64,
+    pub deleted: bool,
+}
+
+/// Look up a recipe whether or not it has been trashed, locking it until `tx` ends
+pub async fn recipe_state(recipe_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Option<RecipeState>> {
+    let state = sqlx::query_as("SELECT user_id, deleted_at IS NOT NULL AS deleted FROM recipes WHERE id = $1 FOR UPDATE;")
+        .bind(recipe_id)
+        .fetch_optional(&mut **tx).await?;
+    Ok(state)
+}
+
+/// Move a recipe to the trash, or remove it for good with `?permanent=true`
+pub async fn handle_delete_request(request: &Request<Vec<u8>>, recipe_id: i64, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let permanent = match QueryParams::from_uri(request.uri()).parse::<bool>("permanent") {
+        Ok(permanent) => permanent.unwrap_or(false),
+        Err(message) => return responses::bad_request_response_with_message(&message),
+    };
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(err) => {
+            log::error!("Error starting delete of recipe {} - {}", recipe_id, err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    let state = match recipe_state(recipe_id, &mut tx).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error checking whether recipe {} exists - {}", recipe_id, err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    // Only permanent deletes can reach into the trash
+    if state.deleted && !permanent {
+        return responses::not_found_response();
+    }
+
+    if !identity.may_modify(state.user_id) {
+        log::info!("{} may not delete recipe {} owned by user {}", identity, recipe_id, state.user_id);
+        return responses::forbidden_response_with_message("Only the recipe's owner or an admin may delete it");
+    }
+
+    match remove_recipe(recipe_id, permanent, identity, tx).await {
+        Ok(_) => {
+            log::info!("Recipe {} {} by {}", recipe_id, if permanent { "deleted" } else { "trashed" }, identity);
+            responses::empty_ok()
+        },
+        Err(err) => {
+            log::error!("Error deleting recipe {} - {}", recipe_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Trash or delete the recipe and record it in the audit log, committing `tx`
+async fn remove_recipe(recipe_id: i64, permanent: bool, identity: &Identity, mut tx: Transaction<'_, Postgres>) -> anyhow::Result<()> {
+    let before = get_recipe::recipe_snapshot(&mut tx, recipe_id).await?;
+    match permanent {
+        true => delete_recipe(recipe_id, &mut tx).await?,
+        false => trash_recipe(recipe_id, &mut tx).await?,
+    }
+    record_recipe_change(&mut tx, identity, recipe_id, Action::Delete, before).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn trash_recipe(recipe_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<()> {
+    sqlx::query("UPDATE recipes
+            SET deleted_at = now()
+            WHERE id = $1 AND deleted_at IS NULL;")
+        .bind(recipe_id)
+        .execute(&mut **tx).await?;
+    Ok(())
+}
+
+async fn delete_recipe(recipe_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM recipe_ingredients WHERE recipe_id = $1;")
+        .bind(recipe_id)
+        .execute(&mut **tx).await?;
+    sqlx::query("DELETE FROM recipes WHERE id = $1;")
+        .bind(recipe_id)
+        .execute(&mut **tx).await?;
+    Ok(())
+}
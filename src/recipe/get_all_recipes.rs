@@ -1,50 +1,353 @@
-use http::Response;
-use sqlx::PgPool;
-use crate::http::responses::{internal_server_error_response, json_ok};
+use std::collections::HashMap;
+use std::str::FromStr;
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use http::{HeaderValue, Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use sqlx::types::Json;
+use crate::http::QueryParams;
+use crate::http::responses::{bad_request_response_with_message, internal_server_error_response, json_ok};
 use crate::recipe::database::RecipeOverview;
+use crate::RankingWeights;
+use crate::recipe::ingredient_filter::IngredientFilter;
+use crate::recipe::ranking::{self, Ranking, ScoreExplanation, VIEWS_AT_RANKED_AT};
+use crate::recipe::tag_filter::TagFilter;
 
-struct ScoredRecipeOverview {
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 200;
+
+const RANK_EXPRESSION: &str = "ts_rank_cd(recipes.search_vector, search.query)";
+const SNIPPET_EXPRESSION: &str = "ts_headline('english',
+    recipe_overviews.brief_description || ' ' || COALESCE(recipes.method, ''),
+    search.query,
+    'StartSel=<mark>, StopSel=</mark>, MaxWords=30, MinWords=10, MaxFragments=2')";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RecipeSort {
+    Newest,
+    Name,
+    /// Weighted ranking signals, see `Ranking`
+    Score,
+    Popularity,
+    /// How well a recipe matches the search, only available when searching
+    Relevance,
+}
+
+impl RecipeSort {
+    /// Expression the list is ordered by before the recipe id, if any
+    fn key_expression(&self, ranking: &Ranking) -> Option<String> {
+        match self {
+            RecipeSort::Newest => None,
+            RecipeSort::Name => Some("recipe_overviews.recipe_name".to_string()),
+            RecipeSort::Score => Some(ranking.score_sql()),
+            RecipeSort::Popularity => Some(VIEWS_AT_RANKED_AT.to_string()),
+            RecipeSort::Relevance => Some(RANK_EXPRESSION.to_string()),
+        }
+    }
+
+    fn ascending(&self) -> bool {
+        matches!(self, RecipeSort::Name)
+    }
+
+    /// Whether the order changes over time, so that later pages must be worked out as of when the first was
+    fn ranked_at_time(&self) -> bool {
+        matches!(self, RecipeSort::Score | RecipeSort::Popularity)
+    }
+
+    fn key_of(&self, recipe: &RecipeListItem) -> Value {
+        match self {
+            RecipeSort::Newest => Value::Null,
+            RecipeSort::Name => Value::from(recipe.recipe_name.clone()),
+            RecipeSort::Score => Value::from(recipe.score.unwrap_or_default()),
+            RecipeSort::Popularity => Value::from(recipe.view_count),
+            RecipeSort::Relevance => Value::from(recipe.rank.unwrap_or_default()),
+        }
+    }
+}
+
+impl FromStr for RecipeSort {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "newest" => Ok(RecipeSort::Newest),
+            "name" => Ok(RecipeSort::Name),
+            "score" => Ok(RecipeSort::Score),
+            "popularity" => Ok(RecipeSort::Popularity),
+            "relevance" => Ok(RecipeSort::Relevance),
+            _ => bail!("Unknown sort '{}' - expected one of newest, name, score, popularity or relevance", value),
+        }
+    }
+}
+
+/// Position after the last recipe of a page, handed to clients as an opaque string
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: RecipeSort,
+    key: Value,
+    id: i64,
+    /// When sorting by score or popularity, the time the first page was ranked at. Scores fall as recipes age and
+    /// views keep coming, so later pages are ranked as of the same time to keep `key` comparable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ranked_at: Option<DateTime<Utc>>,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        BASE64_URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor serializes"))
+    }
+
+    fn decode(cursor: &str, sort: RecipeSort) -> anyhow::Result<Self> {
+        let cursor: Cursor = serde_json::from_slice(&BASE64_URL_SAFE_NO_PAD.decode(cursor)?)?;
+        if cursor.sort != sort {
+            bail!("Cursor is for a different sort order");
+        }
+        let valid_key = match sort {
+            RecipeSort::Newest => cursor.key.is_null(),
+            RecipeSort::Name => cursor.key.is_string(),
+            RecipeSort::Popularity => cursor.key.is_i64(),
+            RecipeSort::Score | RecipeSort::Relevance => cursor.key.is_number(),
+        };
+        if !valid_key {
+            bail!("Cursor key doesn't match its sort order");
+        }
+        if sort.ranked_at_time() && cursor.ranked_at.is_none() {
+            bail!("Cursor is missing the time it was ranked at");
+        }
+        Ok(cursor)
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RecipeListItem {
+    pub recipe_id: i64,
+    pub recipe_name: String,
+    pub brief_description: String,
+    pub image_uri: Option<String>,
+    pub user_id: i64,
+    pub user_name: String,
+    pub tags: Vec<String>,
+    /// Views as of `ranked_at` when sorting by popularity, otherwise all views
+    pub view_count: i64,
+    pub score: Option<f64>,
+    /// Raw ranking signals by name, when sorting by score or explaining scores
+    pub signals: Option<Json<HashMap<String, f64>>>,
+    pub rank: Option<f32>,
+    pub snippet: Option<String>,
+}
+
+/// A recipe overview, plus how well it matched when searching
+#[derive(Debug, Serialize)]
+struct RecipeListEntry {
+    #[serde(flatten)]
     overview: RecipeOverview,
-    score: u16
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rank: Option<f32>,
+    /// Matching text with the matched words wrapped in `<mark>` tags
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+    /// Each signal's part in the recipe's score, when asked for with `explain`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score_explanation: Option<ScoreExplanation>,
 }
 
-pub async fn get_all_recipes(db_pool: &PgPool) -> Response<Vec<u8>> {
-    let recipes = RecipeOverview::get_all_recipe_overviews(db_pool).await;
-    let score_sorted_recipes = match recipes {
-        Ok(recipes) => Ok(score_recipes(recipes)),
-        Err(err) => Err(err)
+impl RecipeListEntry {
+    fn new(recipe: RecipeListItem, ranking: Option<&Ranking>) -> Self {
+        let score_explanation = match (ranking, &recipe.signals) {
+            (Some(ranking), Some(signals)) => Some(ranking.explain(&signals.0)),
+            _ => None,
+        };
+        let overview = RecipeOverview {
+            recipe_id: recipe.recipe_id,
+            recipe_name: recipe.recipe_name,
+            brief_description: recipe.brief_description,
+            image_uri: recipe.image_uri,
+            user_id: recipe.user_id,
+            user_name: recipe.user_name,
+            tags: recipe.tags,
+        };
+        RecipeListEntry { overview, rank: recipe.rank, snippet: recipe.snippet, score_explanation }
+    }
+}
+
+/// A page of recipes ordered by `sort` (newest, name, score, popularity or relevance), at most `limit` long, starting
+/// after `cursor`. `q` searches names, descriptions, methods and ingredient names, matching word prefixes, and sorts
+/// by relevance unless told otherwise. `with_ingredient` and `without_ingredient` filter by ingredient, see
+/// `IngredientFilter`, and `tag` by tag, see `TagFilter`. `explain=true` breaks each recipe's score down by signal.
+/// A `Link` header points to the next page while there is one
+pub async fn get_all_recipes(request: &Request<Vec<u8>>, db_pool: &PgPool, weights: &RankingWeights) -> Response<Vec<u8>> {
+    let params = QueryParams::from_uri(request.uri());
+    let search = match params.get("q").map(search_query) {
+        Some(Some(search)) => Some(search),
+        Some(None) => return bad_request_response_with_message("Search query 'q' contains no words"),
+        None => None,
+    };
+    let (sort, limit, cursor) = match parse_page_params(&params, search.is_some()) {
+        Ok(page) => page,
+        Err(err) => return bad_request_response_with_message(&err.to_string()),
+    };
+    let filter = match IngredientFilter::from_params(&params) {
+        Ok(filter) => filter,
+        Err(err) => return bad_request_response_with_message(&err.to_string()),
     };
-    let json = match score_sorted_recipes {
-        Ok(recipe_vec) => serde_json::to_string(&recipe_vec),
+    let tag_filter = match TagFilter::from_params(&params) {
+        Ok(filter) => filter,
+        Err(err) => return bad_request_response_with_message(&err.to_string()),
+    };
+    let explain = match params.parse::<bool>("explain") {
+        Ok(explain) => explain.unwrap_or(false),
+        Err(message) => return bad_request_response_with_message(&message),
+    };
+    let ranking = Ranking::from_weights(weights);
+    let ranked_at = cursor.as_ref().and_then(|cursor| cursor.ranked_at).unwrap_or_else(Utc::now);
+
+    let page = PageQuery { sort, limit, cursor: cursor.as_ref(), search: search.as_deref(), filter: &filter, tag_filter: &tag_filter, explain, ranked_at };
+    let mut recipes = match fetch_page(db_pool, &page, &ranking).await {
+        Ok(recipes) => recipes,
         Err(err) => {
-            log::info!("Error handling get all recipes request: {}", err);
+            log::error!("Error handling get all recipes request: {}", err);
             return internal_server_error_response()
         }
     };
 
-    match json {
+    // One more row than asked for is fetched to tell whether there is a next page
+    let next_cursor = match recipes.len() as i64 > limit {
+        true => {
+            recipes.truncate(limit as usize);
+            let ranked_at = sort.ranked_at_time().then_some(ranked_at);
+            recipes.last().map(|last| Cursor { sort, key: sort.key_of(last), id: last.recipe_id, ranked_at })
+        },
+        false => None,
+    };
+
+    let explaining = if explain { Some(&ranking) } else { None };
+    let recipes = recipes.into_iter().map(|recipe| RecipeListEntry::new(recipe, explaining)).collect::<Vec<RecipeListEntry>>();
+    let mut response = match serde_json::to_string(&recipes) {
         Ok(json) => json_ok(json),
         Err(err) => {
-            log::info!("Error handling get all recipes request: {}", err);
-            internal_server_error_response()
+            log::error!("Error handling get all recipes request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    if let Some(next_cursor) = next_cursor {
+        let next = format!("<{}?{}>; rel=\"next\"", request.uri().path(), params.to_query_with("cursor", &next_cursor.encode()));
+        if let Ok(next) = HeaderValue::from_str(&next) {
+            response.headers_mut().insert(http::header::LINK, next);
         }
     }
+    response
+}
+
+fn parse_page_params(params: &QueryParams, searching: bool) -> anyhow::Result<(RecipeSort, i64, Option<Cursor>)> {
+    let default_sort = if searching { RecipeSort::Relevance } else { RecipeSort::Newest };
+    let sort = params.parse::<RecipeSort>("sort").map_err(|err| anyhow!(err))?.unwrap_or(default_sort);
+    if sort == RecipeSort::Relevance && !searching {
+        bail!("Sorting by relevance needs a search query 'q'");
+    }
+    let limit = params.parse::<i64>("limit").map_err(|err| anyhow!(err))?.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let cursor = match params.get("cursor") {
+        Some(cursor) => Some(Cursor::decode(cursor, sort).map_err(|err| anyhow!("Invalid cursor - {}", err))?),
+        None => None,
+    };
+    Ok((sort, limit, cursor))
 }
 
-fn score_recipes(recipes: Vec<RecipeOverview>) -> Vec<RecipeOverview> {
-    let mut scored_recipes = recipes.into_iter().map(|recipe_overview| ScoredRecipeOverview {
-        score: score_recipe(&recipe_overview),
-        overview: recipe_overview
-    }).collect::<Vec<ScoredRecipeOverview>>();
-    scored_recipes.sort_by(|a, b| b.score.cmp(&a.score)); // Sort high to low
-    scored_recipes.into_iter().map(|recipe_overview| recipe_overview.overview).collect()
+/// What to fetch for one page of the list
+struct PageQuery<'a> {
+    sort: RecipeSort,
+    limit: i64,
+    cursor: Option<&'a Cursor>,
+    search: Option<&'a str>,
+    filter: &'a IngredientFilter,
+    tag_filter: &'a TagFilter,
+    explain: bool,
+    /// Time signals are worked out as of
+    ranked_at: DateTime<Utc>,
 }
 
-fn score_recipe(recipe: &RecipeOverview) -> u16 {
-    let mut score: u16 = 0;
-    if recipe.image_uri.is_some() {
-        score += 100;
+async fn fetch_page(db_pool: &PgPool, page: &PageQuery<'_>, ranking: &Ranking) -> anyhow::Result<Vec<RecipeListItem>> {
+    let sort = page.sort;
+    // Ranking signals are only worked out when needed, as some are subqueries per recipe
+    let with_signals = sort == RecipeSort::Score || page.explain;
+
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new("SELECT recipe_overviews.*, ");
+    match sort {
+        RecipeSort::Popularity => query.push(VIEWS_AT_RANKED_AT).push(" AS view_count, "),
+        _ => query.push("recipes.view_count, "),
+    };
+    match with_signals {
+        true => query.push(ranking.score_sql()).push(" AS score, to_jsonb(signals) AS signals, "),
+        false => query.push("NULL::FLOAT8 AS score, NULL::JSONB AS signals, "),
+    };
+    match page.search {
+        Some(_) => query.push(RANK_EXPRESSION).push(" AS rank, ").push(SNIPPET_EXPRESSION).push(" AS snippet"),
+        None => query.push("NULL::REAL AS rank, NULL::TEXT AS snippet"),
+    };
+
+    query.push(" FROM recipe_overviews
+        JOIN recipes ON recipes.id = recipe_overviews.recipe_id");
+    if with_signals || sort.ranked_at_time() {
+        ranking::push_ranked_at(&mut query, page.ranked_at);
+    }
+    if with_signals {
+        ranking.push_signals(&mut query);
+    }
+    match page.search {
+        Some(search) => {
+            query.push(" CROSS JOIN (SELECT to_tsquery('english', ").push_bind(search.to_string()).push(") AS query) search
+                WHERE recipes.search_vector @@ search.query");
+        },
+        None => {
+            query.push(" WHERE TRUE");
+        },
+    }
+    page.filter.push_conditions(&mut query);
+    page.tag_filter.push_conditions(&mut query);
+
+    let direction = if sort.ascending() { "ASC" } else { "DESC" };
+    let comparison = if sort.ascending() { ">" } else { "<" };
+    if let Some(cursor) = page.cursor {
+        match sort.key_expression(ranking) {
+            None => {
+                query.push(format!(" AND recipe_overviews.recipe_id {} ", comparison)).push_bind(cursor.id);
+            },
+            Some(key) => {
+                query.push(format!(" AND ({}, recipe_overviews.recipe_id) {} (", key, comparison));
+                match sort {
+                    RecipeSort::Name => query.push_bind(cursor.key.as_str().unwrap_or_default().to_string()),
+                    RecipeSort::Relevance => query.push_bind(cursor.key.as_f64().unwrap_or_default() as f32),
+                    RecipeSort::Score => query.push_bind(cursor.key.as_f64().unwrap_or_default()),
+                    _ => query.push_bind(cursor.key.as_i64().unwrap_or_default()),
+                };
+                query.push(", ").push_bind(cursor.id).push(")");
+            },
+        }
+    }
+
+    query.push(" ORDER BY ");
+    if let Some(key) = sort.key_expression(ranking) {
+        query.push(format!("{} {}, ", key, direction));
     }
-    score
+    query.push(format!("recipe_overviews.recipe_id {} LIMIT ", direction)).push_bind(page.limit + 1);
+
+    let recipes = query.build_query_as().fetch_all(db_pool).await?;
+    Ok(recipes)
 }
 
+/// Turn free text into a tsquery matching every word as a prefix, or None if it has no words
+fn search_query(text: &str) -> Option<String> {
+    let terms = text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word.to_lowercase()))
+        .collect::<Vec<String>>();
+    match terms.is_empty() {
+        true => None,
+        false => Some(terms.join(" & ")),
+    }
+}
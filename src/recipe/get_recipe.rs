@@ -1,40 +1,110 @@
-use crate::http::responses::{bad_request_response, internal_server_error_response, json_ok, not_found_response};
-use crate::recipe::{database};
-use http::Response;
+use crate::http::responses::{bad_request_response, bad_request_response_with_message, internal_server_error_response, json_ok, not_found_response};
+use crate::recipe::{database, recipe_tags, scaling};
+use crate::http::etag::etag;
+use crate::http::QueryParams;
+use http::{HeaderValue, Request, Response};
 use serde::Serialize;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgExecutor, PgPool};
+use crate::recipe::amount::Amount;
 use crate::recipe::database::RecipeIngredientsView;
 
-pub async fn get_recipe_with_id(db_pool: &PgPool, id: &str) -> Response<Vec<u8>> {
+/// The recipe, with its ingredient amounts scaled to `servings` when that is given
+pub async fn get_recipe_with_id(request: &Request<Vec<u8>>, db_pool: &PgPool, id: &str) -> Response<Vec<u8>> {
     let id = match id.parse::<i64>() {
         Ok(id) => id,
         Err(_) => return bad_request_response()
     };
+    let servings = match QueryParams::from_uri(request.uri()).parse::<i32>("servings") {
+        Ok(servings) => servings,
+        Err(message) => return bad_request_response_with_message(&message),
+    };
+    if let Err(message) = scaling::check_servings(servings) {
+        return bad_request_response_with_message(&message);
+    }
 
-    let recipe = GetRecipeResponse::fetch_from_recipe_id(db_pool, id).await;
-    let recipe = match recipe {
+    let recipe = match fetch_versioned_recipe(db_pool, id).await {
         Ok(recipe) => recipe,
         Err(err) => {
-            println!("Error handling get recipe request: {}", err);
+            log::error!("Error handling get recipe request for recipe {} - {}", id, err);
             return internal_server_error_response()
         }
     };
 
-    let recipe = match recipe {
+    let (mut recipe, version) = match recipe {
         Some(recipe) => recipe,
         None => return not_found_response()
     };
+    if let Some(servings) = servings {
+        if let Err(message) = recipe.scale_to(servings) {
+            return bad_request_response_with_message(message);
+        }
+    }
 
     let json = serde_json::to_string(&recipe);
     let json = match json {
         Ok(json) => json,
         Err(err) => {
-            println!("Error handling get recipe request: {}", err);
+            log::error!("Error serializing recipe {} - {}", id, err);
             return internal_server_error_response()
         }
     };
 
-    json_ok(json)
+    if let Err(err) = count_view(db_pool, id).await {
+        log::error!("Error counting view of recipe {} - {}", id, err);
+    }
+
+    let mut response = json_ok(json);
+    if let Ok(etag) = HeaderValue::from_str(&etag(version)) {
+        response.headers_mut().insert(http::header::ETAG, etag);
+    }
+    response
+}
+
+/// Adds to the recipe's total views and to its views this hour in one statement, so the total less recent hours
+/// always gives its views as of an earlier hour
+async fn count_view(db_pool: &PgPool, recipe_id: i64) -> anyhow::Result<()> {
+    sqlx::query("WITH counted AS (
+                UPDATE recipes SET view_count = view_count + 1 WHERE id = $1 RETURNING id
+            )
+            INSERT INTO recipe_view_hours (recipe_id, hour, views)
+            SELECT id, date_trunc('hour', now()), 1 FROM counted
+            ON CONFLICT (recipe_id, hour) DO UPDATE SET views = recipe_view_hours.views + 1;")
+        .bind(recipe_id)
+        .execute(db_pool).await?;
+    Ok(())
+}
+
+async fn recipe_servings<'e>(executor: impl PgExecutor<'e>, recipe_id: i64) -> anyhow::Result<Option<i32>> {
+    let servings = sqlx::query_scalar("SELECT servings FROM recipes WHERE id = $1;")
+        .bind(recipe_id)
+        .fetch_one(executor).await?;
+    Ok(servings)
+}
+
+/// The recipe with its version, read from one snapshot so the ETag always describes the content returned
+async fn fetch_versioned_recipe(db_pool: &PgPool, recipe_id: i64) -> anyhow::Result<Option<(GetRecipeResponse, i64)>> {
+    let mut tx = db_pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY;")
+        .execute(&mut *tx).await?;
+
+    let recipe = match GetRecipeResponse::fetch_from_recipe_id(&mut tx, recipe_id).await? {
+        Some(recipe) => recipe,
+        None => return Ok(None),
+    };
+    let version = sqlx::query_scalar("SELECT version FROM recipes WHERE id = $1;")
+        .bind(recipe_id)
+        .fetch_one(&mut *tx).await?;
+    tx.commit().await?;
+    Ok(Some((recipe, version)))
+}
+
+/// The recipe as returned by GET, for recording in the audit log. Read within the write's transaction so the entry
+/// matches what was written
+pub async fn recipe_snapshot(connection: &mut PgConnection, recipe_id: i64) -> anyhow::Result<Option<serde_json::Value>> {
+    match GetRecipeResponse::fetch_from_recipe_id(connection, recipe_id).await? {
+        Some(recipe) => Ok(Some(serde_json::to_value(recipe)?)),
+        None => Ok(None),
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -46,11 +116,16 @@ struct GetRecipeResponse {
     pub image_uri: Option<String>,
     pub user_id: i64,
     pub user_name: String,
-    pub ingredients: Option<Vec<GetRecipeIngredientsResponse>>
+    pub ingredients: Option<Vec<GetRecipeIngredientsResponse>>,
+    pub tags: Vec<String>,
+    pub servings: Option<i32>,
+    /// The recipe's own servings, when its amounts have been scaled to `servings`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scaled_from_servings: Option<i32>,
 }
 
 impl GetRecipeResponse {
-    pub fn from(recipe_details: database::RecipeDetails, ingredient_details: Option<Vec<GetRecipeIngredientsResponse>>) -> Self {
+    pub fn from(recipe_details: database::RecipeDetails, ingredient_details: Option<Vec<GetRecipeIngredientsResponse>>, tags: Vec<String>, servings: Option<i32>) -> Self {
         let recipe_id = recipe_details.recipe_id;
         let recipe_name = recipe_details.recipe_name;
         let brief_description = recipe_details.brief_description;
@@ -68,20 +143,44 @@ impl GetRecipeResponse {
             image_uri,
             user_id,
             user_name,
-            ingredients
+            ingredients,
+            tags,
+            servings,
+            scaled_from_servings: None
         }
     }
 
-    pub async fn fetch_from_recipe_id(db_pool: &PgPool, recipe_id: i64) -> anyhow::Result<Option<Self>> {
-        let recipe_details = database::RecipeDetails::fetch_from_recipe_id(db_pool, recipe_id).await?;
+    pub async fn fetch_from_recipe_id(connection: &mut PgConnection, recipe_id: i64) -> anyhow::Result<Option<Self>> {
+        let recipe_details = database::RecipeDetails::fetch_from_recipe_id(&mut *connection, recipe_id).await?;
         let recipe_details = match recipe_details {
             Some(recipe_details) => recipe_details,
             None => return Ok(None)
         };
 
-        let recipe_ingredients = database::RecipeIngredientsView::fetch_from_recipe_id(db_pool, recipe_id).await?;
+        let recipe_ingredients = database::RecipeIngredientsView::fetch_from_recipe_id(&mut *connection, recipe_id).await?;
         let ingredient_details = recipe_ingredients.map(|details| details.into_iter().map(GetRecipeIngredientsResponse::from).collect());
-        Ok(Some(Self::from(recipe_details, ingredient_details)))
+        let tags = recipe_tags::tag_names(recipe_id, &mut *connection).await?;
+        let servings = recipe_servings(connection, recipe_id).await?;
+        Ok(Some(Self::from(recipe_details, ingredient_details, tags, servings)))
+    }
+
+    /// Scale the ingredient amounts from the recipe's servings to `servings`. Amounts without a quantity are left
+    /// as they are and marked as not scaled
+    fn scale_to(&mut self, servings: i32) -> Result<(), &'static str> {
+        let recipe_servings = self.servings.ok_or("The recipe doesn't say how many it serves, so can't be scaled")?;
+        let factor = servings as f64 / recipe_servings as f64;
+        for ingredient in self.ingredients.iter_mut().flatten() {
+            match scaling::scale_amount(&ingredient.amount, factor) {
+                Some(amount) => {
+                    ingredient.amount = amount;
+                    ingredient.scaled = Some(true);
+                },
+                None => ingredient.scaled = Some(false),
+            }
+        }
+        self.servings = Some(servings);
+        self.scaled_from_servings = Some(recipe_servings);
+        Ok(())
     }
 }
 
@@ -89,7 +188,12 @@ impl GetRecipeResponse {
 struct GetRecipeIngredientsResponse {
     pub ingredient_id: i64,
     pub ingredient_name: String,
-    pub amount: String
+    /// The quantity, unit and note, with the display form as `amount`
+    #[serde(flatten)]
+    pub amount: Amount,
+    /// Whether the amount was scaled, only given when scaling. Free text amounts with no quantity aren't
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scaled: Option<bool>,
 }
 
 impl From<RecipeIngredientsView> for GetRecipeIngredientsResponse {
@@ -97,6 +201,6 @@ impl From<RecipeIngredientsView> for GetRecipeIngredientsResponse {
         let ingredient_id = recipe_ingredients.ingredient_id;
         let ingredient_name = recipe_ingredients.ingredient_name;
         let amount = recipe_ingredients.amount;
-        Self { ingredient_id, ingredient_name, amount }
+        Self { ingredient_id, ingredient_name, amount, scaled: None }
     }
 }
\ No newline at end of file
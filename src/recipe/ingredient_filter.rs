@@ -0,0 +1,112 @@
+use std::str::FromStr;
+use anyhow::{anyhow, bail};
+use sqlx::{Postgres, QueryBuilder};
+use crate::http::QueryParams;
+
+/// Whether a recipe must match every listed ingredient or just one of them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MatchMode {
+    All,
+    Any,
+}
+
+impl FromStr for MatchMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "all" => Ok(MatchMode::All),
+            "any" => Ok(MatchMode::Any),
+            _ => bail!("Unknown mode '{}' - expected all or any", value),
+        }
+    }
+}
+
+/// An ingredient given by id, or by name ignoring case
+#[derive(Debug, Clone)]
+enum IngredientRef {
+    Id(i64),
+    Name(String),
+}
+
+impl From<&str> for IngredientRef {
+    fn from(value: &str) -> Self {
+        match value.parse::<i64>() {
+            Ok(id) => IngredientRef::Id(id),
+            Err(_) => IngredientRef::Name(value.trim().to_string()),
+        }
+    }
+}
+
+/// `with_ingredient` and `without_ingredient` filters on the recipe list.
+///
+/// Both are repeatable. `with_mode` (default `all`) chooses whether recipes need every `with_ingredient` or any of
+/// them, `without_mode` (default `any`) whether recipes are excluded for having any `without_ingredient` or only all
+/// of them.
+#[derive(Debug)]
+pub struct IngredientFilter {
+    with: Vec<IngredientRef>,
+    with_mode: MatchMode,
+    without: Vec<IngredientRef>,
+    without_mode: MatchMode,
+}
+
+impl IngredientFilter {
+    pub fn from_params(params: &QueryParams) -> anyhow::Result<Self> {
+        let with = params.get_all("with_ingredient").into_iter().map(IngredientRef::from).collect::<Vec<IngredientRef>>();
+        let without = params.get_all("without_ingredient").into_iter().map(IngredientRef::from).collect::<Vec<IngredientRef>>();
+        if with.iter().chain(without.iter()).any(|ingredient| matches!(ingredient, IngredientRef::Name(name) if name.is_empty())) {
+            bail!("Ingredient filters need an ingredient id or name");
+        }
+
+        let with_mode = params.parse::<MatchMode>("with_mode").map_err(|err| anyhow!(err))?.unwrap_or(MatchMode::All);
+        let without_mode = params.parse::<MatchMode>("without_mode").map_err(|err| anyhow!(err))?.unwrap_or(MatchMode::Any);
+        Ok(Self { with, with_mode, without, without_mode })
+    }
+
+    /// Add the filter's conditions to a query over `recipe_overviews`, after its WHERE clause
+    pub fn push_conditions(&self, query: &mut QueryBuilder<Postgres>) {
+        if !self.with.is_empty() {
+            query.push(" AND ");
+            push_match(query, &self.with, self.with_mode);
+        }
+        if !self.without.is_empty() {
+            query.push(" AND NOT ");
+            push_match(query, &self.without, self.without_mode);
+        }
+    }
+}
+
+/// Whether the recipe uses all or any of `ingredients`
+fn push_match(query: &mut QueryBuilder<Postgres>, ingredients: &[IngredientRef], mode: MatchMode) {
+    match mode {
+        MatchMode::All => {
+            query.push("(");
+            for (index, ingredient) in ingredients.iter().enumerate() {
+                if index > 0 {
+                    query.push(" AND ");
+                }
+                push_uses(query, std::slice::from_ref(ingredient));
+            }
+            query.push(")");
+        },
+        MatchMode::Any => push_uses(query, ingredients),
+    }
+}
+
+/// Whether the recipe uses at least one of `ingredients`
+fn push_uses(query: &mut QueryBuilder<Postgres>, ingredients: &[IngredientRef]) {
+    query.push("EXISTS (SELECT 1 FROM recipe_ingredients
+        JOIN ingredients ON ingredients.id = recipe_ingredients.ingredient_id
+        WHERE recipe_ingredients.recipe_id = recipe_overviews.recipe_id AND (");
+    for (index, ingredient) in ingredients.iter().enumerate() {
+        if index > 0 {
+            query.push(" OR ");
+        }
+        match ingredient {
+            IngredientRef::Id(id) => query.push("ingredients.id = ").push_bind(*id),
+            IngredientRef::Name(name) => query.push("lower(ingredients.name) = lower(").push_bind(name.clone()).push(")"),
+        };
+    }
+    query.push("))");
+}
@@ -0,0 +1,127 @@
+use http::{Request, Response};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::types::Json;
+use crate::http::QueryParams;
+use crate::http::responses::{bad_request_response_with_message, internal_server_error_response, json_ok};
+use crate::recipe::database::RecipeOverview;
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MissingIngredient {
+    pub ingredient_id: i64,
+    pub ingredient_name: String,
+}
+
+/// A recipe and how much of it can be made from the ingredients at hand
+#[derive(Debug, Serialize)]
+pub struct RecipeMatch {
+    #[serde(flatten)]
+    pub overview: RecipeOverview,
+    /// Percentage of the recipe's ingredients at hand
+    pub coverage: f64,
+    pub ingredient_count: i64,
+    pub matched_count: i64,
+    pub missing_ingredients: Vec<MissingIngredient>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RecipeMatchItem {
+    pub recipe_id: i64,
+    pub recipe_name: String,
+    pub brief_description: String,
+    pub image_uri: Option<String>,
+    pub user_id: i64,
+    pub user_name: String,
+    pub tags: Vec<String>,
+    pub coverage: f64,
+    pub ingredient_count: i64,
+    pub matched_count: i64,
+    pub missing_ingredients: Json<Vec<MissingIngredient>>,
+}
+
+impl From<RecipeMatchItem> for RecipeMatch {
+    fn from(item: RecipeMatchItem) -> Self {
+        let overview = RecipeOverview {
+            recipe_id: item.recipe_id,
+            recipe_name: item.recipe_name,
+            brief_description: item.brief_description,
+            image_uri: item.image_uri,
+            user_id: item.user_id,
+            user_name: item.user_name,
+            tags: item.tags,
+        };
+        RecipeMatch {
+            overview,
+            coverage: item.coverage,
+            ingredient_count: item.ingredient_count,
+            matched_count: item.matched_count,
+            missing_ingredients: item.missing_ingredients.0,
+        }
+    }
+}
+
+/// Recipes using any of the `ingredient` ids (repeatable or comma separated), best covered first
+pub async fn get_recipe_matches(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
+    let params = QueryParams::from_uri(request.uri());
+    let ingredient_ids = match params.get_all("ingredient").iter().flat_map(|value| value.split(',')).map(|id| id.trim().parse::<i64>()).collect::<Result<Vec<i64>, _>>() {
+        Ok(ids) if !ids.is_empty() => ids,
+        Ok(_) => return bad_request_response_with_message("Give the ingredients at hand as 'ingredient' ids"),
+        Err(_) => return bad_request_response_with_message("Ingredient ids must be integers"),
+    };
+    let limit = match params.parse::<i64>("limit") {
+        Ok(limit) => limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        Err(message) => return bad_request_response_with_message(&message),
+    };
+
+    match_recipes_response(db_pool, &ingredient_ids, limit).await
+}
+
+pub async fn match_recipes_response(db_pool: &PgPool, ingredient_ids: &[i64], limit: i64) -> Response<Vec<u8>> {
+    let matches = match match_recipes(db_pool, ingredient_ids, limit).await {
+        Ok(matches) => matches,
+        Err(err) => {
+            log::error!("Error handling recipe match request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    match serde_json::to_string(&matches) {
+        Ok(json) => json_ok(json),
+        Err(err) => {
+            log::error!("Error handling recipe match request: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
+
+/// Rank recipes by the share of their ingredients in `ingredient_ids`, ignoring those using none of them
+pub async fn match_recipes(db_pool: &PgPool, ingredient_ids: &[i64], limit: i64) -> anyhow::Result<Vec<RecipeMatch>> {
+    let matches: Vec<RecipeMatchItem> = sqlx::query_as("SELECT recipe_overviews.*,
+                counts.matched_count::FLOAT8 * 100 / counts.ingredient_count AS coverage,
+                counts.ingredient_count,
+                counts.matched_count,
+                COALESCE(missing.ingredients, '[]'::JSONB) AS missing_ingredients
+            FROM recipe_overviews
+            JOIN (
+                SELECT recipe_id,
+                    COUNT(*) AS ingredient_count,
+                    COUNT(*) FILTER (WHERE ingredient_id = ANY($1)) AS matched_count
+                FROM recipe_ingredients_list
+                GROUP BY recipe_id
+            ) counts ON counts.recipe_id = recipe_overviews.recipe_id
+            LEFT JOIN LATERAL (
+                SELECT jsonb_agg(jsonb_build_object('ingredient_id', ingredient_id, 'ingredient_name', ingredient_name) ORDER BY ingredient_name) AS ingredients
+                FROM recipe_ingredients_list
+                WHERE recipe_id = recipe_overviews.recipe_id AND NOT ingredient_id = ANY($1)
+            ) missing ON TRUE
+            WHERE counts.matched_count > 0
+            ORDER BY coverage DESC, counts.ingredient_count - counts.matched_count ASC, recipe_overviews.recipe_id DESC
+            LIMIT $2;")
+        .bind(ingredient_ids)
+        .bind(limit)
+        .fetch_all(db_pool).await?;
+    Ok(matches.into_iter().map(RecipeMatch::from).collect())
+}
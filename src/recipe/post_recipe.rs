@@ -1,6 +1,12 @@
+use anyhow::anyhow;
 use http::Request;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Postgres, Transaction};
+use crate::audit::Action;
+use crate::authorization::Identity;
+use crate::recipe::record_recipe_change;
+use crate::recipe::{recipe_tags, revision, scaling};
+use crate::recipe::amount::Amount;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PostRecipeRequestData {
@@ -8,14 +14,21 @@ struct PostRecipeRequestData {
     pub brief_description: String,
     pub image_uri: Option<String>,
     pub method: Option<String>,
-    pub user_id: i64,
+    /// How many the recipe serves
+    pub servings: Option<i32>,
+    /// Only used when authenticating with a token rather than as a user
+    pub user_id: Option<i64>,
     pub ingredients: Option<Vec<PostIngredientRequestData>>,
+    /// Names of existing tags
+    pub tags: Option<Vec<String>>,
 }
 
+/// The amount is checked as it is read, see `Amount`
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct PostIngredientRequestData {
     pub ingredient_id: i64,
-    pub amount: String,
+    #[serde(flatten)]
+    pub amount: Amount,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,16 +40,23 @@ pub struct PostRecipeResponseData {
 struct RecipeIngredientData {
     pub recipe_id: i64,
     pub ingredient_id: i64,
-    pub amount: String,
+    pub amount: Amount,
 }
 
-pub async fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> anyhow::Result<PostRecipeResponseData> {
+pub async fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<PostRecipeResponseData> {
     let put_recipe_request: PostRecipeRequestData = serde_json::from_slice(request.body())?;
+    scaling::check_servings(put_recipe_request.servings).map_err(|message| anyhow!(message))?;
+
+    // Users always create recipes as themselves, tokens act on behalf of the given user
+    let user_id = match identity.user_id {
+        Some(user_id) => user_id,
+        None => put_recipe_request.user_id.ok_or(anyhow!("user_id is required when authenticating with a token"))?,
+    };
 
     let mut tx = db_pool.begin().await?;
 
     // Add recipe to recipes table
-    let inserted_recipe_id = insert_recipe(&put_recipe_request, &mut tx).await?;
+    let inserted_recipe_id = insert_recipe(&put_recipe_request, user_id, &mut tx).await?;
 
     if let Some(ingredients) = put_recipe_request.ingredients {
         for ingredient in ingredients {
@@ -49,6 +69,12 @@ pub async fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -
         }
     }
 
+    if let Some(tags) = &put_recipe_request.tags {
+        recipe_tags::set_tags(inserted_recipe_id, tags, &mut tx).await?;
+    }
+
+    revision::record_revision(inserted_recipe_id, identity, &mut tx).await?;
+    record_recipe_change(&mut tx, identity, inserted_recipe_id, Action::Create, None).await?;
     tx.commit().await?;
 
     Ok(PostRecipeResponseData { recipe_id: inserted_recipe_id })
@@ -59,16 +85,17 @@ struct InsertedRecipe {
     id: i64
 }
 
-async fn insert_recipe(recipe: &PostRecipeRequestData, transaction: &mut Transaction<'_, Postgres>) -> anyhow::Result<i64> {
+async fn insert_recipe(recipe: &PostRecipeRequestData, user_id: i64, transaction: &mut Transaction<'_, Postgres>) -> anyhow::Result<i64> {
     let inserted_recipe: InsertedRecipe = sqlx::query_as("INSERT INTO recipes
-            (name, brief_description, method, image_uri, user_id)
-            VALUES ($1, $2, $3, $4, $5)
+            (name, brief_description, method, image_uri, servings, user_id)
+            VALUES ($1, $2, $3, $4, $5, $6)
             RETURNING id;")
         .bind(&recipe.recipe_name)
         .bind(&recipe.brief_description)
         .bind(&recipe.method)
         .bind(&recipe.image_uri)
-        .bind(&recipe.user_id)
+        .bind(recipe.servings)
+        .bind(user_id)
         .fetch_one(&mut **transaction).await?;
     Ok(inserted_recipe.id)
 }
@@ -80,12 +107,15 @@ struct InsertedRecipeIngredient {
 
 async fn insert_ingredient_recipe(recipe_ingredient_data: &RecipeIngredientData, transaction: &mut Transaction<'_, Postgres>) -> anyhow::Result<i64> {
     let inserted_row: InsertedRecipeIngredient = sqlx::query_as("INSERT INTO recipe_ingredients
-            (recipe_id, ingredient_id, amount)
-            VALUES ($1, $2)
+            (recipe_id, ingredient_id, amount, quantity, unit, note)
+            VALUES ($1, $2, $3, $4, $5, $6)
             RETURNING id;")
-        .bind(&recipe_ingredient_data.recipe_id)
-        .bind(&recipe_ingredient_data.ingredient_id)
-        .bind(&recipe_ingredient_data.amount)
+        .bind(recipe_ingredient_data.recipe_id)
+        .bind(recipe_ingredient_data.ingredient_id)
+        .bind(recipe_ingredient_data.amount.to_string())
+        .bind(recipe_ingredient_data.amount.quantity)
+        .bind(recipe_ingredient_data.amount.unit.map(|unit| unit.as_str()))
+        .bind(&recipe_ingredient_data.amount.note)
         .fetch_one(&mut **transaction).await?;
     Ok(inserted_row.id)
 }
\ No newline at end of file
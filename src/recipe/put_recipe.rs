@@ -1,188 +1,223 @@
-use http::{Request, Response};
-use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use http::{Method, Request, Response};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::audit::Action;
+use crate::authorization::Identity;
+use crate::http::etag::{etag, if_match_allows};
+use crate::http::merge_patch::apply_merge_patch;
 use crate::http::responses;
-use crate::recipe::chunk_url;
-use crate::recipe::database::recipe_overview;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct PutRecipeRequestData {
-    pub recipe_name: Option<String>,
-    pub brief_description: Option<String>,
-    pub image_uri: Option<String>,
-    pub method: Option<String>,
-    pub user_id: Option<i64>,
+use crate::recipe::recipe_ingredients::{self, IngredientChange, RecipeEditError};
+use crate::recipe::{get_recipe, record_recipe_change, recipe_tags, scaling};
+use crate::recipe::revision::{self, RecipeContent};
+
+#[derive(Debug, sqlx::FromRow)]
+struct CurrentRecipe {
+    pub user_id: i64,
+    pub version: i64,
 }
 
-pub async fn handle_put_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
-    log::debug!("Handling PUT request for {}", request.uri());
+/// Where an update's new content comes from
+pub enum UpdateSource {
+    /// A full replacement (PUT) or merge patch (PATCH) in the request body
+    Body,
+    /// A stored revision being restored
+    Revision(i64),
+}
 
-    let uri_chunks = chunk_url(request.uri());
-    if uri_chunks.len() != 2 {
-        let message = "Request uri should contain 2 chunks";
-        log::info!("{}", message);
-        return responses::bad_request_response_with_message(message);
-    }
+/// Content to write and the ingredient edits that go with it
+struct RecipeUpdate {
+    content: RecipeContent,
+    replace_ingredients: bool,
+    ingredient_changes: Vec<IngredientChange>,
+}
+
+/// PUT replaces the whole recipe, PATCH applies a JSON Merge Patch where null clears a field, and restoring a
+/// revision replaces the recipe with that revision. Each update is made in a single transaction, only if `If-Match`
+/// holds the recipe's current ETag, and is stored as a new revision
+pub async fn handle_update_request(request: &Request<Vec<u8>>, recipe_id: i64, source: UpdateSource, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    log::debug!("Handling {} request for {}", request.method(), request.uri());
 
-    let recipe_id = uri_chunks[1];
-    let recipe_id = match recipe_id.parse::<i64>() {
-        Ok(id) => id,
+    let if_match = match request.headers().get(http::header::IF_MATCH) {
+        Some(if_match) => if_match,
+        None => return responses::precondition_required_response_with_message("Updates must give the recipe's ETag in If-Match"),
+    };
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
         Err(err) => {
-            log::info!("Failed parsing recipe id {} - {}", recipe_id, err);
-            return responses::bad_request_response_with_message("Recipe id must be an integer");
+            log::error!("Error starting update of recipe {} - {}", recipe_id, err);
+            return responses::internal_server_error_response();
         }
     };
 
-    let recipe_exists = match recipe_with_id_exists(recipe_id, db_pool).await {
-        Ok(exists) => exists,
+    let current = match lock_recipe(recipe_id, &mut tx).await {
+        Ok(Some(current)) => current,
+        Ok(None) => {
+            log::debug!("Recipe did not exist for request {}", request.uri());
+            return responses::not_found_response();
+        },
         Err(err) => {
             log::error!("Error checking whether recipe {} exists - {}", recipe_id, err);
             return responses::internal_server_error_response();
         }
     };
 
-    if !recipe_exists {
-        log::debug!("Recipe did not exist for request {}", request.uri());
-        return responses::not_found_response();
+    if !identity.may_modify(current.user_id) {
+        log::info!("{} may not edit recipe {} owned by user {}", identity, recipe_id, current.user_id);
+        return responses::forbidden_response_with_message("Only the recipe's owner or an admin may edit it");
     }
 
-    let put_recipe_request: PutRecipeRequestData = match serde_json::from_slice(request.body()) {
-        Ok(data) => data,
+    if !if_match_allows(if_match, &etag(current.version)) {
+        log::info!("Recipe {} changed since {} last read it", recipe_id, identity);
+        return responses::precondition_failed_response_with_message("The recipe has changed since it was read");
+    }
+
+    let before = match get_recipe::recipe_snapshot(&mut tx, recipe_id).await {
+        Ok(before) => before,
         Err(err) => {
-            log::error!("Error deserializing request response body - {}", err);
+            log::error!("Error reading recipe {} for the audit log - {}", recipe_id, err);
             return responses::internal_server_error_response();
         }
     };
 
-    if let Some(recipe_name) = put_recipe_request.recipe_name {
-        match update_recipe_name(recipe_id, &recipe_name, db_pool).await {
-            Ok(_) => (),
+    let update = match source {
+        UpdateSource::Revision(revision) => match revision::revision_content(recipe_id, revision, &mut tx).await {
+            Ok(Some(content)) => match restorable_content(content, &mut tx).await {
+                Ok(content) => Ok(RecipeUpdate { content, replace_ingredients: true, ingredient_changes: vec![] }),
+                Err(err) => {
+                    log::error!("Error checking the tags and ingredients of revision {} of recipe {} - {}", revision, recipe_id, err);
+                    return responses::internal_server_error_response();
+                }
+            },
+            Ok(None) => return responses::not_found_response(),
             Err(err) => {
-                log::error!("Error updating recipe name for recipe {} - {}", recipe_id, err);
-                return responses::internal_server_error_response();
-            }
-        }
-    }
-
-    if let Some(brief_description) = put_recipe_request.brief_description {
-        match update_brief_description(recipe_id, &brief_description, db_pool).await {
-            Ok(_) => (),
-            Err(err) => {
-                log::error!("Error updating brief description for recipe {} - {}", recipe_id, err);
+                log::error!("Error reading revision {} of recipe {} - {}", revision, recipe_id, err);
                 return responses::internal_server_error_response();
             }
+        },
+        UpdateSource::Body => match *request.method() {
+            Method::PATCH => parse_patch(request.body(), recipe_id, revision::current_content(recipe_id, &mut tx).await),
+            _ => parse_replacement(request.body()),
+        },
+    };
+    let update = match update.and_then(|update| scaling::check_servings(update.content.servings).map(|_| update)) {
+        Ok(update) => update,
+        Err(message) => {
+            log::info!("Invalid update for recipe {} - {}", recipe_id, message);
+            return responses::bad_request_response_with_message(&message);
         }
-    }
+    };
 
-    if let Some(image_uri) = put_recipe_request.image_uri {
-        match update_image_uri(recipe_id, &image_uri, db_pool).await {
-            Ok(_) => (),
-            Err(err) => {
-                log::error!("Error updating image uri for recipe {} - {}", recipe_id, err);
-                return responses::internal_server_error_response();
-            }
-        }
+    if update.content.user_id.is_some_and(|user_id| user_id != current.user_id) && !identity.is_admin() {
+        log::info!("{} may not reassign the owner of recipe {}", identity, recipe_id);
+        return responses::forbidden_response_with_message("Only an admin may change a recipe's owner");
     }
 
-    if let Some(recipe_method) = put_recipe_request.method {
-        match update_recipe_method(recipe_id, &recipe_method, db_pool).await {
-            Ok(_) => (),
-            Err(err) => {
-                log::error!("Error updating method for recipe {} - {}", recipe_id, err);
-                return responses::internal_server_error_response();
-            }
+    let version = match write_update(recipe_id, &update, identity, &mut tx).await {
+        Ok(version) => version,
+        Err(RecipeEditError::Database(err)) => {
+            log::error!("Error updating recipe {} - {}", recipe_id, err);
+            return responses::internal_server_error_response();
+        },
+        Err(err) => {
+            log::info!("Invalid ingredient edit for recipe {} - {}", recipe_id, err);
+            return responses::bad_request_response_with_message(&err.to_string());
         }
+    };
+    if let Err(err) = record_recipe_change(&mut tx, identity, recipe_id, Action::Update, before).await {
+        log::error!("Error writing audit log entry for update of recipe {} - {}", recipe_id, err);
+        return responses::internal_server_error_response();
     }
 
-    if let Some(user_id) = put_recipe_request.user_id {
-        match update_user_id(recipe_id, user_id, db_pool).await {
-            Ok(_) => (),
-            Err(err) => {
-                log::error!("Error updating user id for recipe {} - {}", recipe_id, err);
-                return responses::internal_server_error_response();
-            }
+    match tx.commit().await {
+        Ok(_) => http::Response::builder()
+            .status(http::status::StatusCode::OK)
+            .header(http::header::ETAG, etag(version))
+            .body(vec![])
+            .expect("error building response"),
+        Err(err) => {
+            log::error!("Error committing update of recipe {} - {}", recipe_id, err);
+            responses::internal_server_error_response()
         }
     }
-
-    responses::empty_ok()
 }
 
-async fn recipe_with_id_exists(recipe_id: i64, db_pool: &PgPool) -> anyhow::Result<bool> {
-    let recipe = recipe_overview::RecipeOverview::get_recipe_overview(recipe_id, db_pool).await?;
-    Ok(recipe.is_some())
+fn parse_replacement(body: &[u8]) -> Result<RecipeUpdate, String> {
+    let content: RecipeContent = serde_json::from_slice(body).map_err(|err| format!("Invalid request body - {}", err))?;
+    Ok(RecipeUpdate { content, replace_ingredients: true, ingredient_changes: vec![] })
 }
 
-async fn update_recipe_name(recipe_id: i64, recipe_name: &str, db_pool: &PgPool) -> anyhow::Result<()> {
-
-    let result = sqlx::query!("UPDATE recipes
-            SET name = $1
-            WHERE id = $2;",
-        recipe_name, recipe_id)
-        .execute(db_pool).await?;
+/// Merge the patch into the recipe's current content. `ingredient_changes` isn't part of the content, so it is
+/// taken out of the patch and applied to the ingredient list afterwards
+fn parse_patch(body: &[u8], recipe_id: i64, current: anyhow::Result<RecipeContent>) -> Result<RecipeUpdate, String> {
+    let mut patch: Value = serde_json::from_slice(body).map_err(|err| format!("Invalid request body - {}", err))?;
+    let patch_fields = match patch.as_object_mut() {
+        Some(fields) => fields,
+        None => return Err("Patch must be a JSON object".to_string()),
+    };
 
-    if result.rows_affected() == 0 {
-        log::error!("Update image request for recipe {} updated 0 rows", recipe_id);
+    let ingredient_changes: Vec<IngredientChange> = match patch_fields.remove("ingredient_changes") {
+        Some(changes) => serde_json::from_value(changes).map_err(|err| format!("Invalid ingredient_changes - {}", err))?,
+        None => vec![],
+    };
+    let replace_ingredients = patch_fields.contains_key("ingredients");
+    if replace_ingredients && !ingredient_changes.is_empty() {
+        return Err("Give either ingredients or ingredient_changes, not both".to_string());
     }
 
-    Ok(())
+    let mut content = current.and_then(|current| Ok(serde_json::to_value(current)?)).map_err(|err| {
+        log::error!("Error reading recipe {} to patch - {}", recipe_id, err);
+        "Failed to read the current recipe".to_string()
+    })?;
+    apply_merge_patch(&mut content, &patch);
+    let content: RecipeContent = serde_json::from_value(content).map_err(|err| format!("Invalid patch - {}", err))?;
+    Ok(RecipeUpdate { content, replace_ingredients, ingredient_changes })
 }
 
-async fn update_brief_description(recipe_id: i64, brief_description: &str, db_pool: &PgPool) -> anyhow::Result<()> {
-
-    let result = sqlx::query!("UPDATE recipes
-            SET brief_description = $1
-            WHERE id = $2;",
-        brief_description, recipe_id)
-        .execute(db_pool).await?;
-
-    if result.rows_affected() == 0 {
-        log::error!("Update image request for recipe {} updated 0 rows", recipe_id);
-    }
-
-    Ok(())
+/// A revision's content as it can be restored now. Ownership isn't part of a recipe's history, so restoring keeps
+/// the current owner. Tags deleted since the revision are left off and ingredients merged since are replaced by the
+/// ingredient they were merged into, rather than failing the restore
+async fn restorable_content(content: RecipeContent, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<RecipeContent> {
+    let tags = recipe_tags::existing_tag_names(&content.tags, tx).await?;
+    let ingredients = recipe_ingredients::follow_merges(content.ingredients, tx).await?;
+    Ok(RecipeContent { user_id: None, tags, ingredients, ..content })
 }
 
-async fn update_image_uri(recipe_id: i64, image_uri: &str, db_pool: &PgPool) -> anyhow::Result<()> {
-
-    let result = sqlx::query!("UPDATE recipes
-            SET image_uri = $1
-            WHERE id = $2;",
-        image_uri, recipe_id)
-        .execute(db_pool).await?;
-
-    if result.rows_affected() == 0 {
-        log::error!("Update image request for recipe {} updated 0 rows", recipe_id);
-    }
-
-    Ok(())
+/// The recipe row, locked until the update commits
+async fn lock_recipe(recipe_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Option<CurrentRecipe>> {
+    let recipe = sqlx::query_as("SELECT user_id, version
+            FROM recipes
+            WHERE id = $1 AND deleted_at IS NULL
+            FOR UPDATE;")
+        .bind(recipe_id)
+        .fetch_optional(&mut **tx).await?;
+    Ok(recipe)
 }
 
-async fn update_recipe_method(recipe_id: i64, recipe_method: &str, db_pool: &PgPool) -> anyhow::Result<()> {
-
-    let result = sqlx::query!("UPDATE recipes
-            SET method = $1
-            WHERE id = $2;",
-        recipe_method, recipe_id)
-        .execute(db_pool).await?;
-
-    if result.rows_affected() == 0 {
-        log::error!("Update image request for recipe {} updated 0 rows", recipe_id);
+/// Returns the recipe's new version
+async fn write_update(recipe_id: i64, update: &RecipeUpdate, identity: &Identity, tx: &mut Transaction<'_, Postgres>) -> Result<i64, RecipeEditError> {
+    let content = &update.content;
+    let version: i64 = sqlx::query_scalar("UPDATE recipes
+            SET name = $1, brief_description = $2, method = $3, image_uri = $4, servings = $5,
+                user_id = COALESCE($6, user_id), version = version + 1
+            WHERE id = $7
+            RETURNING version;")
+        .bind(&content.recipe_name)
+        .bind(&content.brief_description)
+        .bind(&content.method)
+        .bind(&content.image_uri)
+        .bind(content.servings)
+        .bind(content.user_id)
+        .bind(recipe_id)
+        .fetch_one(&mut **tx).await?;
+
+    if update.replace_ingredients {
+        recipe_ingredients::replace_ingredients(recipe_id, &content.ingredients, tx).await?;
     }
-
-    Ok(())
-}
-
-async fn update_user_id(recipe_id: i64, user_id: i64, db_pool: &PgPool) -> anyhow::Result<()> {
-
-    let result = sqlx::query!("UPDATE recipes
-            SET user_id = $1
-            WHERE id = $2;",
-        user_id, recipe_id)
-        .execute(db_pool).await?;
-
-    if result.rows_affected() == 0 {
-        log::error!("Update image request for recipe {} updated 0 rows", recipe_id);
+    if !update.ingredient_changes.is_empty() {
+        recipe_ingredients::apply_ingredient_changes(recipe_id, &update.ingredient_changes, tx).await?;
     }
-
-    Ok(())
-}
\ No newline at end of file
+    recipe_tags::set_tags(recipe_id, &content.tags, tx).await?;
+    revision::record_revision(recipe_id, identity, tx).await.map_err(RecipeEditError::Database)?;
+    Ok(version)
+}
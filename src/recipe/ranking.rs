@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Postgres, QueryBuilder};
+use crate::RankingWeights;
+
+/// Ingredient count at which the ingredients signal scores 0.5
+const INGREDIENTS_HALF_SCORE: f64 = 5.0;
+/// Favorite count at which the favorites signal scores 0.5
+const FAVORITES_HALF_SCORE: f64 = 10.0;
+/// View count at which the views signal scores 0.5
+const VIEWS_HALF_SCORE: f64 = 100.0;
+/// Age in days at which the recency signal has halved
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+/// Ratings are averaged as if every recipe also had this many ratings of `RATING_PRIOR_MEAN`, so a single rating
+/// can't put a recipe at the top
+const RATING_PRIOR_COUNT: i64 = 5;
+const RATING_PRIOR_MEAN: i64 = 3;
+const MIN_RATING: f64 = 1.0;
+const MAX_RATING: f64 = 5.0;
+
+/// A recipe's views before the hour `ranked_at.at` falls in. Views are still being counted for that hour, but the
+/// hours before it are settled, so every page of a listing sees the same counts
+pub const VIEWS_AT_RANKED_AT: &str = "(recipes.view_count - COALESCE((SELECT sum(recipe_view_hours.views)
+        FROM recipe_view_hours
+        WHERE recipe_view_hours.recipe_id = recipes.id
+        AND recipe_view_hours.hour >= date_trunc('hour', ranked_at.at)), 0))::BIGINT";
+
+/// One signal a recipe's score is built from. The raw signal is read in SQL and mapped to a score from 0 to 1,
+/// which is done both in SQL, to sort by it, and in Rust, to explain it
+pub trait Scorer {
+    /// Name of the signal, as a SQL column and in explanations
+    fn name(&self) -> &'static str;
+    /// SQL for the raw signal, with `recipe_overviews` joined to `recipes` in scope. Signals depending on the time
+    /// use `ranked_at.at` rather than `now()`, so pages of a ranking all score recipes as of the same moment
+    fn signal_sql(&self) -> String;
+    /// SQL mapping the raw signal to its score, matching `score`
+    fn score_sql(&self, signal: &str) -> String;
+    fn score(&self, signal: f64) -> f64;
+}
+
+/// Whether the recipe has an image
+pub struct HasImage;
+
+impl Scorer for HasImage {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    fn signal_sql(&self) -> String {
+        "(recipe_overviews.image_uri IS NOT NULL)::INT::FLOAT8".to_string()
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        flag_sql(signal)
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        flag(signal)
+    }
+}
+
+/// Whether the recipe has a method that isn't blank
+pub struct HasMethod;
+
+impl Scorer for HasMethod {
+    fn name(&self) -> &'static str {
+        "method"
+    }
+
+    fn signal_sql(&self) -> String {
+        "(COALESCE(btrim(recipes.method), '') <> '')::INT::FLOAT8".to_string()
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        flag_sql(signal)
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        flag(signal)
+    }
+}
+
+/// Number of ingredients, with diminishing returns
+pub struct IngredientCount;
+
+impl Scorer for IngredientCount {
+    fn name(&self) -> &'static str {
+        "ingredients"
+    }
+
+    fn signal_sql(&self) -> String {
+        "(SELECT count(*) FROM recipe_ingredients WHERE recipe_ingredients.recipe_id = recipes.id)::FLOAT8".to_string()
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        saturating_sql(signal, INGREDIENTS_HALF_SCORE)
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        saturating(signal, INGREDIENTS_HALF_SCORE)
+    }
+}
+
+/// Average rating from 1 to 5, pulled towards the middle while there are few ratings
+pub struct Rating;
+
+impl Scorer for Rating {
+    fn name(&self) -> &'static str {
+        "rating"
+    }
+
+    fn signal_sql(&self) -> String {
+        format!("(SELECT (COALESCE(sum(recipe_ratings.rating), 0) + {})::FLOAT8 / (count(*) + {})
+            FROM recipe_ratings
+            WHERE recipe_ratings.recipe_id = recipes.id)", RATING_PRIOR_MEAN * RATING_PRIOR_COUNT, RATING_PRIOR_COUNT)
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        format!("LEAST(GREATEST(({} - {}) / {}, 0), 1)", signal, sql_float(MIN_RATING), sql_float(MAX_RATING - MIN_RATING))
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        ((signal - MIN_RATING) / (MAX_RATING - MIN_RATING)).clamp(0.0, 1.0)
+    }
+}
+
+/// Number of users who favorited the recipe, with diminishing returns
+pub struct Favorites;
+
+impl Scorer for Favorites {
+    fn name(&self) -> &'static str {
+        "favorites"
+    }
+
+    fn signal_sql(&self) -> String {
+        "(SELECT count(*) FROM recipe_favorites WHERE recipe_favorites.recipe_id = recipes.id)::FLOAT8".to_string()
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        saturating_sql(signal, FAVORITES_HALF_SCORE)
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        saturating(signal, FAVORITES_HALF_SCORE)
+    }
+}
+
+/// Age of the recipe in days, scoring 1 when new and halving every `RECENCY_HALF_LIFE_DAYS`
+pub struct Recency;
+
+impl Scorer for Recency {
+    fn name(&self) -> &'static str {
+        "recency"
+    }
+
+    fn signal_sql(&self) -> String {
+        "(EXTRACT(EPOCH FROM ranked_at.at - recipes.created_at) / 86400)::FLOAT8".to_string()
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        format!("power(0.5::FLOAT8, GREATEST({}, 0) / {})", signal, sql_float(RECENCY_HALF_LIFE_DAYS))
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        0.5_f64.powf(signal.max(0.0) / RECENCY_HALF_LIFE_DAYS)
+    }
+}
+
+/// Views of the recipe's page, with diminishing returns
+pub struct Views;
+
+impl Scorer for Views {
+    fn name(&self) -> &'static str {
+        "views"
+    }
+
+    fn signal_sql(&self) -> String {
+        format!("{}::FLOAT8", VIEWS_AT_RANKED_AT)
+    }
+
+    fn score_sql(&self, signal: &str) -> String {
+        saturating_sql(signal, VIEWS_HALF_SCORE)
+    }
+
+    fn score(&self, signal: f64) -> f64 {
+        saturating(signal, VIEWS_HALF_SCORE)
+    }
+}
+
+/// How one signal added to a recipe's score
+#[derive(Debug, Serialize)]
+pub struct SignalContribution {
+    pub signal: &'static str,
+    pub value: f64,
+    pub score: f64,
+    pub weight: f64,
+    pub contribution: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreExplanation {
+    pub total: f64,
+    pub signals: Vec<SignalContribution>,
+}
+
+/// Weighted scorers making up a recipe's score. Signals with a weight of 0 are left out
+pub struct Ranking {
+    scorers: Vec<(Box<dyn Scorer>, f64)>,
+}
+
+impl Ranking {
+    pub fn from_weights(weights: &RankingWeights) -> Self {
+        let scorers: Vec<(Box<dyn Scorer>, f64)> = vec![
+            (Box::new(HasImage), weights.image),
+            (Box::new(HasMethod), weights.method),
+            (Box::new(IngredientCount), weights.ingredients),
+            (Box::new(Rating), weights.rating),
+            (Box::new(Favorites), weights.favorites),
+            (Box::new(Recency), weights.recency),
+            (Box::new(Views), weights.views),
+        ];
+        Ranking { scorers: scorers.into_iter().filter(|(_, weight)| *weight > 0.0).collect() }
+    }
+
+    /// Join the raw signals, as they were at `ranked_at` joined in by `push_ranked_at`, in as `signals`, one column
+    /// per scorer
+    pub fn push_signals(&self, query: &mut QueryBuilder<Postgres>) {
+        let columns = self.scorers.iter()
+            .map(|(scorer, _)| format!("{} AS {}", scorer.signal_sql(), scorer.name()))
+            .collect::<Vec<String>>();
+        query.push(format!(" CROSS JOIN LATERAL (SELECT {}) signals", columns.join(", ")));
+    }
+
+    /// SQL for the weighted score, given the joined `signals`
+    pub fn score_sql(&self) -> String {
+        if self.scorers.is_empty() {
+            return "0::FLOAT8".to_string();
+        }
+        let terms = self.scorers.iter()
+            .map(|(scorer, weight)| format!("{} * {}", sql_float(*weight), scorer.score_sql(&format!("signals.{}", scorer.name()))))
+            .collect::<Vec<String>>();
+        format!("({})", terms.join(" + "))
+    }
+
+    /// Break a score down by signal, given the raw signals by name
+    pub fn explain(&self, signals: &HashMap<String, f64>) -> ScoreExplanation {
+        let signals = self.scorers.iter()
+            .map(|(scorer, weight)| {
+                let value = signals.get(scorer.name()).copied().unwrap_or_default();
+                let score = scorer.score(value);
+                SignalContribution { signal: scorer.name(), value, score, weight: *weight, contribution: weight * score }
+            })
+            .collect::<Vec<SignalContribution>>();
+        ScoreExplanation { total: signals.iter().map(|signal| signal.contribution).sum(), signals }
+    }
+}
+
+/// Join the time signals are worked out as of in as `ranked_at.at`
+pub fn push_ranked_at(query: &mut QueryBuilder<Postgres>, ranked_at: DateTime<Utc>) {
+    query.push(" CROSS JOIN (SELECT ").push_bind(ranked_at).push("::TIMESTAMPTZ AS at) ranked_at");
+}
+
+fn flag(signal: f64) -> f64 {
+    if signal > 0.0 { 1.0 } else { 0.0 }
+}
+
+fn flag_sql(signal: &str) -> String {
+    format!("(CASE WHEN {} > 0 THEN 1 ELSE 0 END)::FLOAT8", signal)
+}
+
+/// 0 at 0, `half` scores 0.5, approaching 1 as the signal grows
+fn saturating(signal: f64, half: f64) -> f64 {
+    let signal = signal.max(0.0);
+    signal / (signal + half)
+}
+
+fn saturating_sql(signal: &str, half: f64) -> String {
+    format!("(GREATEST({0}, 0) / (GREATEST({0}, 0) + {1}))", signal, sql_float(half))
+}
+
+/// A float literal for SQL. Weights are checked to be finite when the config is read
+fn sql_float(value: f64) -> String {
+    format!("{}::FLOAT8", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {} but got {}", expected, actual);
+    }
+
+    #[test]
+    fn has_image_scores_one_with_an_image() {
+        assert_close(HasImage.score(1.0), 1.0);
+        assert_close(HasImage.score(0.0), 0.0);
+    }
+
+    #[test]
+    fn has_method_scores_one_with_a_method() {
+        assert_close(HasMethod.score(1.0), 1.0);
+        assert_close(HasMethod.score(0.0), 0.0);
+    }
+
+    #[test]
+    fn ingredient_count_has_diminishing_returns() {
+        assert_close(IngredientCount.score(0.0), 0.0);
+        assert_close(IngredientCount.score(INGREDIENTS_HALF_SCORE), 0.5);
+        assert!(IngredientCount.score(20.0) > IngredientCount.score(10.0));
+        assert!(IngredientCount.score(1000.0) < 1.0);
+    }
+
+    #[test]
+    fn rating_maps_one_to_five_stars_onto_zero_to_one() {
+        assert_close(Rating.score(MIN_RATING), 0.0);
+        assert_close(Rating.score(3.0), 0.5);
+        assert_close(Rating.score(MAX_RATING), 1.0);
+        assert_close(Rating.score(0.0), 0.0);
+    }
+
+    #[test]
+    fn favorites_have_diminishing_returns() {
+        assert_close(Favorites.score(0.0), 0.0);
+        assert_close(Favorites.score(FAVORITES_HALF_SCORE), 0.5);
+        assert!(Favorites.score(50.0) > Favorites.score(20.0));
+    }
+
+    #[test]
+    fn recency_halves_every_half_life() {
+        assert_close(Recency.score(0.0), 1.0);
+        assert_close(Recency.score(RECENCY_HALF_LIFE_DAYS), 0.5);
+        assert_close(Recency.score(2.0 * RECENCY_HALF_LIFE_DAYS), 0.25);
+        // Clock skew can make a new recipe look slightly in the future
+        assert_close(Recency.score(-1.0), 1.0);
+    }
+
+    #[test]
+    fn views_have_diminishing_returns() {
+        assert_close(Views.score(0.0), 0.0);
+        assert_close(Views.score(VIEWS_HALF_SCORE), 0.5);
+        assert!(Views.score(1000.0) > Views.score(500.0));
+    }
+
+    #[test]
+    fn ranking_leaves_out_signals_weighted_zero() {
+        let ranking = Ranking::from_weights(&RankingWeights { image: 0.0, views: 0.0, ..RankingWeights::default() });
+        let names = ranking.scorers.iter().map(|(scorer, _)| scorer.name()).collect::<Vec<&str>>();
+        assert_eq!(names, ["method", "ingredients", "rating", "favorites", "recency"]);
+        assert!(!ranking.score_sql().contains("signals.image"));
+    }
+
+    #[test]
+    fn explanation_adds_up_weighted_scores() {
+        let weights = RankingWeights { image: 2.0, method: 1.0, ingredients: 0.0, rating: 0.0, favorites: 0.0, recency: 0.0, views: 4.0 };
+        let signals = HashMap::from([("image".to_string(), 1.0), ("method".to_string(), 0.0), ("views".to_string(), VIEWS_HALF_SCORE)]);
+        let explanation = Ranking::from_weights(&weights).explain(&signals);
+
+        let contributions = explanation.signals.iter().map(|signal| (signal.signal, signal.contribution)).collect::<Vec<(&str, f64)>>();
+        assert_eq!(contributions, [("image", 2.0), ("method", 0.0), ("views", 2.0)]);
+        assert_close(explanation.total, 4.0);
+    }
+}
@@ -0,0 +1,137 @@
+use http::{Request, Response};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::authorization::Identity;
+use crate::http::responses;
+
+const MIN_RATING: i16 = 1;
+const MAX_RATING: i16 = 5;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RatingRequestData {
+    pub rating: i16,
+}
+
+/// Sets the caller's rating of the recipe, replacing any earlier rating
+pub async fn handle_put_rating_request(request: &Request<Vec<u8>>, recipe_id: i64, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match feedback_user(identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+
+    let rating: RatingRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse rating request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+    if !(MIN_RATING..=MAX_RATING).contains(&rating.rating) {
+        return responses::bad_request_response_with_message(&format!("Rating must be between {} and {}", MIN_RATING, MAX_RATING));
+    }
+
+    let result = sqlx::query("INSERT INTO recipe_ratings (recipe_id, user_id, rating)
+            SELECT id, $2, $3 FROM recipes WHERE id = $1 AND deleted_at IS NULL
+            ON CONFLICT (recipe_id, user_id) DO UPDATE SET rating = EXCLUDED.rating, rated_at = now();")
+        .bind(recipe_id)
+        .bind(user_id)
+        .bind(rating.rating)
+        .execute(db_pool).await;
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("{} rated recipe {} {}", identity, recipe_id, rating.rating);
+            responses::empty_ok()
+        },
+        Ok(_) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error rating recipe {} for user {} - {}", recipe_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+pub async fn handle_delete_rating_request(recipe_id: i64, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match feedback_user(identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+
+    let result = sqlx::query("DELETE FROM recipe_ratings WHERE recipe_id = $1 AND user_id = $2;")
+        .bind(recipe_id)
+        .bind(user_id)
+        .execute(db_pool).await;
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("{} removed their rating of recipe {}", identity, recipe_id);
+            responses::empty_ok()
+        },
+        Ok(_) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error removing rating of recipe {} for user {} - {}", recipe_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Adds the recipe to the caller's favorites. Favoriting a recipe twice leaves it favorited once
+pub async fn handle_put_favorite_request(recipe_id: i64, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match feedback_user(identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+
+    let result: Result<i64, sqlx::Error> = sqlx::query_scalar("WITH recipe AS (
+                SELECT id FROM recipes WHERE id = $1 AND deleted_at IS NULL
+            ), favorited AS (
+                INSERT INTO recipe_favorites (recipe_id, user_id)
+                SELECT id, $2 FROM recipe
+                ON CONFLICT (recipe_id, user_id) DO NOTHING
+            )
+            SELECT count(*) FROM recipe;")
+        .bind(recipe_id)
+        .bind(user_id)
+        .fetch_one(db_pool).await;
+    match result {
+        Ok(found) if found > 0 => {
+            log::info!("{} favorited recipe {}", identity, recipe_id);
+            responses::empty_ok()
+        },
+        Ok(_) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error favoriting recipe {} for user {} - {}", recipe_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+pub async fn handle_delete_favorite_request(recipe_id: i64, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match feedback_user(identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+
+    let result = sqlx::query("DELETE FROM recipe_favorites WHERE recipe_id = $1 AND user_id = $2;")
+        .bind(recipe_id)
+        .bind(user_id)
+        .execute(db_pool).await;
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("{} unfavorited recipe {}", identity, recipe_id);
+            responses::empty_ok()
+        },
+        Ok(_) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error unfavoriting recipe {} for user {} - {}", recipe_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Ratings and favorites are a user's own, so tokens not acting as a user can't give them
+fn feedback_user(identity: &Identity) -> Result<i64, Box<Response<Vec<u8>>>> {
+    identity.user_id.ok_or_else(|| {
+        log::info!("{} has no user to rate or favorite recipes as", identity);
+        Box::new(responses::forbidden_response_with_message("Only users may rate and favorite recipes"))
+    })
+}
@@ -0,0 +1,193 @@
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use crate::recipe::amount::Amount;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeIngredientData {
+    pub ingredient_id: i64,
+    #[serde(flatten)]
+    pub amount: Amount,
+}
+
+/// A single edit to a recipe's ingredient list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum IngredientChange {
+    Add {
+        ingredient_id: i64,
+        #[serde(flatten)]
+        amount: Amount,
+    },
+    Remove { ingredient_id: i64 },
+    SetAmount {
+        ingredient_id: i64,
+        #[serde(flatten)]
+        amount: Amount,
+    },
+}
+
+impl IngredientChange {
+    fn ingredient_id(&self) -> i64 {
+        match self {
+            IngredientChange::Add { ingredient_id, .. } => *ingredient_id,
+            IngredientChange::Remove { ingredient_id } => *ingredient_id,
+            IngredientChange::SetAmount { ingredient_id, .. } => *ingredient_id,
+        }
+    }
+}
+
+/// Why an edit to a recipe's ingredients or tags was rejected
+#[derive(Debug)]
+pub enum RecipeEditError {
+    UnknownIngredients(Vec<i64>),
+    UnknownTags(Vec<String>),
+    DuplicateIngredient(i64),
+    AlreadyInRecipe(i64),
+    NotInRecipe(i64),
+    Database(anyhow::Error),
+}
+
+impl Display for RecipeEditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeEditError::UnknownIngredients(ids) => {
+                let ids = ids.iter().map(i64::to_string).collect::<Vec<String>>().join(", ");
+                f.write_fmt(format_args!("Unknown ingredient id(s) {}", ids))
+            },
+            RecipeEditError::UnknownTags(names) =>
+                f.write_fmt(format_args!("Unknown tag(s) {}", names.join(", "))),
+            RecipeEditError::DuplicateIngredient(id) =>
+                f.write_fmt(format_args!("Ingredient {} is listed more than once", id)),
+            RecipeEditError::AlreadyInRecipe(id) =>
+                f.write_fmt(format_args!("Ingredient {} is already in the recipe", id)),
+            RecipeEditError::NotInRecipe(id) =>
+                f.write_fmt(format_args!("Ingredient {} is not in the recipe", id)),
+            RecipeEditError::Database(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for RecipeEditError {}
+
+impl From<sqlx::Error> for RecipeEditError {
+    fn from(err: sqlx::Error) -> Self {
+        RecipeEditError::Database(err.into())
+    }
+}
+
+/// Replace the recipe's whole ingredient list
+pub async fn replace_ingredients(recipe_id: i64, ingredients: &[RecipeIngredientData], tx: &mut Transaction<'_, Postgres>) -> Result<(), RecipeEditError> {
+    let ids = ingredients.iter().map(|ingredient| ingredient.ingredient_id).collect::<Vec<i64>>();
+    if let Some(duplicate) = ids.iter().enumerate().find(|(index, id)| ids[..*index].contains(id)).map(|(_, id)| *id) {
+        return Err(RecipeEditError::DuplicateIngredient(duplicate));
+    }
+    check_ingredients_exist(&ids, tx).await?;
+
+    sqlx::query("DELETE FROM recipe_ingredients WHERE recipe_id = $1;")
+        .bind(recipe_id)
+        .execute(&mut **tx).await?;
+    for ingredient in ingredients {
+        insert_ingredient(recipe_id, ingredient.ingredient_id, &ingredient.amount, tx).await?;
+    }
+    Ok(())
+}
+
+/// The ingredients with any since merged away replaced by the ingredient they were merged into. Revisions keep the
+/// ids their ingredients had, and where a merge leaves an ingredient listed twice the first listing is kept, as the
+/// merge itself does
+pub async fn follow_merges(ingredients: Vec<RecipeIngredientData>, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Vec<RecipeIngredientData>> {
+    let ids = ingredients.iter().map(|ingredient| ingredient.ingredient_id).collect::<Vec<i64>>();
+    let merges: Vec<(i64, i64)> = sqlx::query_as("SELECT merged_id, into_id FROM ingredient_merges WHERE merged_id = ANY($1);")
+        .bind(&ids)
+        .fetch_all(&mut **tx).await?;
+
+    let mut followed: Vec<RecipeIngredientData> = vec![];
+    for mut ingredient in ingredients {
+        if let Some((_, into)) = merges.iter().find(|(merged, _)| *merged == ingredient.ingredient_id) {
+            ingredient.ingredient_id = *into;
+        }
+        if !followed.iter().any(|kept| kept.ingredient_id == ingredient.ingredient_id) {
+            followed.push(ingredient);
+        }
+    }
+    Ok(followed)
+}
+
+/// Apply add, remove and change amount operations in order
+pub async fn apply_ingredient_changes(recipe_id: i64, changes: &[IngredientChange], tx: &mut Transaction<'_, Postgres>) -> Result<(), RecipeEditError> {
+    let ids = changes.iter().map(IngredientChange::ingredient_id).collect::<Vec<i64>>();
+    check_ingredients_exist(&ids, tx).await?;
+
+    for change in changes {
+        match change {
+            IngredientChange::Add { ingredient_id, amount } => {
+                if is_in_recipe(recipe_id, *ingredient_id, tx).await? {
+                    return Err(RecipeEditError::AlreadyInRecipe(*ingredient_id));
+                }
+                insert_ingredient(recipe_id, *ingredient_id, amount, tx).await?;
+            },
+            IngredientChange::Remove { ingredient_id } => {
+                let result = sqlx::query("DELETE FROM recipe_ingredients WHERE recipe_id = $1 AND ingredient_id = $2;")
+                    .bind(recipe_id)
+                    .bind(ingredient_id)
+                    .execute(&mut **tx).await?;
+                if result.rows_affected() == 0 {
+                    return Err(RecipeEditError::NotInRecipe(*ingredient_id));
+                }
+            },
+            IngredientChange::SetAmount { ingredient_id, amount } => {
+                let result = sqlx::query("UPDATE recipe_ingredients
+                        SET amount = $1, quantity = $2, unit = $3, note = $4
+                        WHERE recipe_id = $5 AND ingredient_id = $6;")
+                    .bind(amount.to_string())
+                    .bind(amount.quantity)
+                    .bind(amount.unit.map(|unit| unit.as_str()))
+                    .bind(&amount.note)
+                    .bind(recipe_id)
+                    .bind(ingredient_id)
+                    .execute(&mut **tx).await?;
+                if result.rows_affected() == 0 {
+                    return Err(RecipeEditError::NotInRecipe(*ingredient_id));
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+async fn check_ingredients_exist(ids: &[i64], tx: &mut Transaction<'_, Postgres>) -> Result<(), RecipeEditError> {
+    let existing: Vec<i64> = sqlx::query_scalar("SELECT id FROM ingredients WHERE id = ANY($1);")
+        .bind(ids)
+        .fetch_all(&mut **tx).await?;
+
+    let mut unknown = ids.iter().filter(|id| !existing.contains(id)).copied().collect::<Vec<i64>>();
+    unknown.sort();
+    unknown.dedup();
+    match unknown.is_empty() {
+        true => Ok(()),
+        false => Err(RecipeEditError::UnknownIngredients(unknown)),
+    }
+}
+
+async fn is_in_recipe(recipe_id: i64, ingredient_id: i64, tx: &mut Transaction<'_, Postgres>) -> Result<bool, RecipeEditError> {
+    let found: Option<i64> = sqlx::query_scalar("SELECT id FROM recipe_ingredients WHERE recipe_id = $1 AND ingredient_id = $2;")
+        .bind(recipe_id)
+        .bind(ingredient_id)
+        .fetch_optional(&mut **tx).await?;
+    Ok(found.is_some())
+}
+
+async fn insert_ingredient(recipe_id: i64, ingredient_id: i64, amount: &Amount, tx: &mut Transaction<'_, Postgres>) -> Result<(), RecipeEditError> {
+    sqlx::query("INSERT INTO recipe_ingredients
+            (recipe_id, ingredient_id, amount, quantity, unit, note)
+            VALUES ($1, $2, $3, $4, $5, $6);")
+        .bind(recipe_id)
+        .bind(ingredient_id)
+        .bind(amount.to_string())
+        .bind(amount.quantity)
+        .bind(amount.unit.map(|unit| unit.as_str()))
+        .bind(&amount.note)
+        .execute(&mut **tx).await?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use sqlx::{PgExecutor, Postgres, Transaction};
+use crate::authorization::Identity;
+use crate::recipe::recipe_ingredients::RecipeEditError;
+use crate::recipe::revision;
+
+/// The recipe's tag names, alphabetically
+pub async fn tag_names<'e>(recipe_id: i64, executor: impl PgExecutor<'e>) -> anyhow::Result<Vec<String>> {
+    let names = sqlx::query_scalar("SELECT tags.name
+            FROM recipe_tags
+            JOIN tags ON tags.id = recipe_tags.tag_id
+            WHERE recipe_tags.recipe_id = $1
+            ORDER BY tags.name;")
+        .bind(recipe_id)
+        .fetch_all(executor).await?;
+    Ok(names)
+}
+
+/// The names that are still tags, ignoring case. Revisions keep the names their tags had, which may since have
+/// been deleted or renamed
+pub async fn existing_tag_names(names: &[String], tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Vec<String>> {
+    let lower_names = names.iter().map(|name| name.trim().to_lowercase()).collect::<Vec<String>>();
+    let names = sqlx::query_scalar("SELECT name FROM tags WHERE lower(name) = ANY($1) ORDER BY name;")
+        .bind(&lower_names)
+        .fetch_all(&mut **tx).await?;
+    Ok(names)
+}
+
+/// Recipes with the tag, trashed or not, locked until `tx` ends
+pub async fn tagged_recipe_ids(tag_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Vec<i64>> {
+    let recipe_ids = sqlx::query_scalar("SELECT recipes.id
+            FROM recipes
+            JOIN recipe_tags ON recipe_tags.recipe_id = recipes.id
+            WHERE recipe_tags.tag_id = $1
+            ORDER BY recipes.id
+            FOR UPDATE OF recipes;")
+        .bind(tag_id)
+        .fetch_all(&mut **tx).await?;
+    Ok(recipe_ids)
+}
+
+/// Give recipes changed from outside, such as by a tag being renamed or an ingredient merged, a new version stored
+/// as a revision, so ETags read before the change no longer match
+pub async fn bump_recipe_versions(recipe_ids: &[i64], identity: &Identity, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<()> {
+    sqlx::query("UPDATE recipes SET version = version + 1 WHERE id = ANY($1);")
+        .bind(recipe_ids)
+        .execute(&mut **tx).await?;
+    for recipe_id in recipe_ids {
+        revision::record_revision(*recipe_id, identity, tx).await?;
+    }
+    Ok(())
+}
+
+/// Replace the recipe's tags with the existing tags named, ignoring case
+pub async fn set_tags(recipe_id: i64, names: &[String], tx: &mut Transaction<'_, Postgres>) -> Result<(), RecipeEditError> {
+    let mut lower_names = names.iter().map(|name| name.trim().to_lowercase()).collect::<Vec<String>>();
+    lower_names.sort();
+    lower_names.dedup();
+
+    let tags: Vec<(i64, String)> = sqlx::query_as("SELECT id, lower(name) FROM tags WHERE lower(name) = ANY($1);")
+        .bind(&lower_names)
+        .fetch_all(&mut **tx).await?;
+
+    let unknown = lower_names.iter()
+        .filter(|name| !tags.iter().any(|(_, tag_name)| tag_name == *name))
+        .cloned()
+        .collect::<Vec<String>>();
+    if !unknown.is_empty() {
+        return Err(RecipeEditError::UnknownTags(unknown));
+    }
+
+    sqlx::query("DELETE FROM recipe_tags WHERE recipe_id = $1;")
+        .bind(recipe_id)
+        .execute(&mut **tx).await?;
+    sqlx::query("INSERT INTO recipe_tags (recipe_id, tag_id) SELECT $1, unnest($2::BIGINT[]);")
+        .bind(recipe_id)
+        .bind(tags.iter().map(|(tag_id, _)| *tag_id).collect::<Vec<i64>>())
+        .execute(&mut **tx).await?;
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use http::Response;
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::audit::Action;
+use crate::authorization::Identity;
+use crate::http::responses;
+use crate::recipe::delete_recipe::recipe_state;
+use crate::recipe::record_recipe_change;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct TrashedRecipeItem {
+    pub recipe_id: i64,
+    pub recipe_name: String,
+    pub brief_description: String,
+    pub image_uri: Option<String>,
+    pub user_id: i64,
+    pub user_name: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Trashed recipes the caller may restore - all of them for an admin, otherwise their own
+pub async fn get_trash(db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let owner = match identity.is_admin() {
+        true => None,
+        false => match identity.user_id {
+            Some(user_id) => Some(user_id),
+            None => return responses::forbidden_response_with_message("Only users or admins have a trash"),
+        },
+    };
+
+    let recipes: Vec<TrashedRecipeItem> = match sqlx::query_as("SELECT recipes.id AS recipe_id,
+                recipes.name AS recipe_name,
+                recipes.brief_description,
+                recipes.image_uri,
+                recipes.user_id,
+                users.name AS user_name,
+                recipes.deleted_at
+            FROM recipes
+            JOIN users ON users.id = recipes.user_id
+            WHERE recipes.deleted_at IS NOT NULL
+            AND ($1::BIGINT IS NULL OR recipes.user_id = $1)
+            ORDER BY recipes.deleted_at DESC;")
+        .bind(owner)
+        .fetch_all(db_pool).await {
+        Ok(recipes) => recipes,
+        Err(err) => {
+            log::error!("Error handling get recipe trash request: {}", err);
+            return responses::internal_server_error_response()
+        }
+    };
+
+    match serde_json::to_string(&recipes) {
+        Ok(json) => responses::json_ok(json),
+        Err(err) => {
+            log::error!("Error handling get recipe trash request: {}", err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+pub async fn handle_restore_request(recipe_id: i64, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(err) => {
+            log::error!("Error starting restore of recipe {} - {}", recipe_id, err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    let state = match recipe_state(recipe_id, &mut tx).await {
+        Ok(Some(state)) if state.deleted => state,
+        Ok(_) => return responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error checking whether recipe {} is in the trash - {}", recipe_id, err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    if !identity.may_modify(state.user_id) {
+        log::info!("{} may not restore recipe {} owned by user {}", identity, recipe_id, state.user_id);
+        return responses::forbidden_response_with_message("Only the recipe's owner or an admin may restore it");
+    }
+
+    match restore_recipe(recipe_id, identity, tx).await {
+        Ok(_) => {
+            log::info!("Recipe {} restored by {}", recipe_id, identity);
+            responses::empty_ok()
+        },
+        Err(err) => {
+            log::error!("Error restoring recipe {} - {}", recipe_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Take the recipe out of the trash and record it in the audit log, committing `tx`
+async fn restore_recipe(recipe_id: i64, identity: &Identity, mut tx: Transaction<'_, Postgres>) -> anyhow::Result<()> {
+    sqlx::query("UPDATE recipes
+            SET deleted_at = NULL
+            WHERE id = $1;")
+        .bind(recipe_id)
+        .execute(&mut *tx).await?;
+    record_recipe_change(&mut tx, identity, recipe_id, Action::Restore, None).await?;
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use http::Response;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::audit;
+use crate::authorization::Identity;
+use crate::http::responses::{internal_server_error_response, json_ok, not_found_response};
+use crate::recipe::amount::Amount;
+use crate::recipe::recipe_ingredients::RecipeIngredientData;
+use crate::recipe::recipe_tags;
+
+/// Everything about a recipe an update can change, and what each revision stores
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecipeContent {
+    pub recipe_name: String,
+    pub brief_description: String,
+    pub method: Option<String>,
+    pub image_uri: Option<String>,
+    /// How many the recipe serves, which its amounts are scaled from
+    pub servings: Option<i32>,
+    /// Owner of the recipe, left unchanged by an update when missing
+    pub user_id: Option<i64>,
+    #[serde(default)]
+    pub ingredients: Vec<RecipeIngredientData>,
+    /// Tag names. Missing or null means none, so replacing a recipe without them, patching them to null or
+    /// restoring a revision from before tags clears them
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub tags: Vec<String>,
+}
+
+fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(Option::<Vec<String>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct RevisionSummary {
+    pub revision: i64,
+    pub created_at: DateTime<Utc>,
+    pub actor_name: String,
+    pub actor_user_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct Revision {
+    pub revision: i64,
+    pub created_at: DateTime<Utc>,
+    pub actor_name: String,
+    pub actor_user_id: Option<i64>,
+    pub content: Value,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RecipeRow {
+    pub name: String,
+    pub brief_description: String,
+    pub method: Option<String>,
+    pub image_uri: Option<String>,
+    pub servings: Option<i32>,
+    pub user_id: i64,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct IngredientRow {
+    pub ingredient_id: i64,
+    pub quantity: Option<f64>,
+    pub unit: Option<String>,
+    pub note: Option<String>,
+    pub amount: String,
+}
+
+/// The recipe as it stands within `tx`
+pub async fn current_content(recipe_id: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<RecipeContent> {
+    let recipe: RecipeRow = sqlx::query_as("SELECT name, brief_description, method, image_uri, servings, user_id FROM recipes WHERE id = $1;")
+        .bind(recipe_id)
+        .fetch_one(&mut **tx).await?;
+    let ingredients: Vec<IngredientRow> = sqlx::query_as("SELECT ingredient_id, quantity, unit, note, amount
+            FROM recipe_ingredients
+            WHERE recipe_id = $1
+            ORDER BY id;")
+        .bind(recipe_id)
+        .fetch_all(&mut **tx).await?;
+    let tags = recipe_tags::tag_names(recipe_id, &mut **tx).await?;
+
+    Ok(RecipeContent {
+        recipe_name: recipe.name,
+        brief_description: recipe.brief_description,
+        method: recipe.method,
+        image_uri: recipe.image_uri,
+        servings: recipe.servings,
+        user_id: Some(recipe.user_id),
+        ingredients: ingredients.into_iter()
+            .map(|row| RecipeIngredientData { ingredient_id: row.ingredient_id, amount: Amount::from_columns(row.quantity, row.unit, row.note, &row.amount) })
+            .collect(),
+        tags,
+    })
+}
+
+/// Store the recipe as it stands within `tx` as the revision for its current version
+pub async fn record_revision(recipe_id: i64, identity: &Identity, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<()> {
+    let content = current_content(recipe_id, tx).await?;
+    sqlx::query("INSERT INTO recipe_revisions
+            (recipe_id, revision, content, actor_name, actor_user_id)
+            VALUES ($1, (SELECT version FROM recipes WHERE id = $1), $2, $3, $4);")
+        .bind(recipe_id)
+        .bind(serde_json::to_value(content)?)
+        .bind(&identity.name)
+        .bind(identity.user_id)
+        .execute(&mut **tx).await?;
+    Ok(())
+}
+
+/// Content of a stored revision, to restore it
+pub async fn revision_content(recipe_id: i64, revision: i64, tx: &mut Transaction<'_, Postgres>) -> anyhow::Result<Option<RecipeContent>> {
+    let content: Option<Value> = sqlx::query_scalar("SELECT content FROM recipe_revisions WHERE recipe_id = $1 AND revision = $2;")
+        .bind(recipe_id)
+        .bind(revision)
+        .fetch_optional(&mut **tx).await?;
+    match content {
+        Some(content) => Ok(Some(serde_json::from_value(content)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_revisions(db_pool: &PgPool, recipe_id: i64) -> Response<Vec<u8>> {
+    let revisions: Vec<RevisionSummary> = match sqlx::query_as("SELECT recipe_revisions.revision,
+                recipe_revisions.created_at,
+                recipe_revisions.actor_name,
+                recipe_revisions.actor_user_id
+            FROM recipe_revisions
+            JOIN recipes ON recipes.id = recipe_revisions.recipe_id
+            WHERE recipe_revisions.recipe_id = $1 AND recipes.deleted_at IS NULL
+            ORDER BY recipe_revisions.revision DESC;")
+        .bind(recipe_id)
+        .fetch_all(db_pool).await {
+        Ok(revisions) => revisions,
+        Err(err) => {
+            log::error!("Error handling get revisions request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    if revisions.is_empty() {
+        return not_found_response();
+    }
+    to_json_response(&revisions)
+}
+
+pub async fn get_revision(db_pool: &PgPool, recipe_id: i64, revision: i64) -> Response<Vec<u8>> {
+    match fetch_revision(db_pool, recipe_id, revision).await {
+        Ok(Some(revision)) => to_json_response(&revision),
+        Ok(None) => not_found_response(),
+        Err(err) => {
+            log::error!("Error handling get revision request: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
+
+/// Changed fields, plus ingredients added, removed or given a different amount, going from one revision to another
+pub async fn get_revision_diff(db_pool: &PgPool, recipe_id: i64, from: i64, to: i64) -> Response<Vec<u8>> {
+    let (from_revision, to_revision) = match (fetch_revision(db_pool, recipe_id, from).await, fetch_revision(db_pool, recipe_id, to).await) {
+        (Ok(Some(from_revision)), Ok(Some(to_revision))) => (from_revision, to_revision),
+        (Ok(_), Ok(_)) => return not_found_response(),
+        (Err(err), _) | (_, Err(err)) => {
+            log::error!("Error handling get revision diff request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    let (from_content, to_content) = match (serde_json::from_value::<RecipeContent>(from_revision.content), serde_json::from_value::<RecipeContent>(to_revision.content)) {
+        (Ok(from_content), Ok(to_content)) => (from_content, to_content),
+        (Err(err), _) | (_, Err(err)) => {
+            log::error!("Stored revision of recipe {} is invalid - {}", recipe_id, err);
+            return internal_server_error_response()
+        }
+    };
+
+    let diff = json!({
+        "from": from,
+        "to": to,
+        "changes": field_diff(&from_content, &to_content),
+        "ingredients": ingredient_diff(&from_content.ingredients, &to_content.ingredients),
+    });
+    to_json_response(&diff)
+}
+
+async fn fetch_revision(db_pool: &PgPool, recipe_id: i64, revision: i64) -> anyhow::Result<Option<Revision>> {
+    let revision = sqlx::query_as("SELECT recipe_revisions.revision,
+                recipe_revisions.created_at,
+                recipe_revisions.actor_name,
+                recipe_revisions.actor_user_id,
+                recipe_revisions.content
+            FROM recipe_revisions
+            JOIN recipes ON recipes.id = recipe_revisions.recipe_id
+            WHERE recipe_revisions.recipe_id = $1 AND recipe_revisions.revision = $2 AND recipes.deleted_at IS NULL;")
+        .bind(recipe_id)
+        .bind(revision)
+        .fetch_optional(db_pool).await?;
+    Ok(revision)
+}
+
+fn field_diff(from: &RecipeContent, to: &RecipeContent) -> Value {
+    let fields = |content: &RecipeContent| {
+        let mut fields = serde_json::to_value(content).unwrap_or_default();
+        if let Some(fields) = fields.as_object_mut() {
+            fields.remove("ingredients");
+        }
+        fields
+    };
+    audit::diff(Some(&fields(from)), Some(&fields(to)))
+}
+
+fn ingredient_diff(from: &[RecipeIngredientData], to: &[RecipeIngredientData]) -> Value {
+    let find = |ingredients: &[RecipeIngredientData], ingredient_id: i64| {
+        ingredients.iter().find(|ingredient| ingredient.ingredient_id == ingredient_id).cloned()
+    };
+
+    let added = to.iter().filter(|ingredient| find(from, ingredient.ingredient_id).is_none()).cloned().collect::<Vec<_>>();
+    let removed = from.iter().filter(|ingredient| find(to, ingredient.ingredient_id).is_none()).cloned().collect::<Vec<_>>();
+    let changed = from.iter()
+        .filter_map(|before| find(to, before.ingredient_id).filter(|after| after.amount != before.amount).map(|after| json!({
+            "ingredient_id": before.ingredient_id,
+            "before": before.amount,
+            "after": after.amount,
+        })))
+        .collect::<Vec<Value>>();
+    json!({ "added": added, "removed": removed, "changed": changed })
+}
+
+fn to_json_response<T: Serialize>(value: &T) -> Response<Vec<u8>> {
+    match serde_json::to_string(value) {
+        Ok(json) => json_ok(json),
+        Err(err) => {
+            log::error!("Error serializing revision response: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
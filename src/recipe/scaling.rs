@@ -0,0 +1,201 @@
+use crate::recipe::amount::{Amount, Unit, DISPLAY_FRACTIONS};
+
+pub const MAX_SERVINGS: i32 = 100;
+/// Quantities at least this large are rounded to whole numbers rather than fractions
+const WHOLE_NUMBERS_FROM: f64 = 10.0;
+
+/// Units measuring the same thing, largest first, each with how many of the family's smallest unit it holds and the
+/// least quantity worth writing in it. Scaled amounts move to the largest unit they fill, so 12 tsp becomes 1/4 cup
+/// and half of 1 tbsp becomes 1 1/2 tsp. Metric and imperial units are never mixed
+const UNIT_FAMILIES: [&[(Unit, f64, f64)]; 4] = [
+    &[(Unit::Cup, 48.0, 0.25), (Unit::Tablespoon, 3.0, 1.0), (Unit::Teaspoon, 1.0, 0.0)],
+    &[(Unit::Kilogram, 1000.0, 1.0), (Unit::Gram, 1.0, 0.0)],
+    &[(Unit::Litre, 1000.0, 1.0), (Unit::Millilitre, 1.0, 0.0)],
+    &[(Unit::Pound, 16.0, 1.0), (Unit::Ounce, 1.0, 0.0)],
+];
+
+/// Servings given for a recipe must be between 1 and `MAX_SERVINGS`
+pub fn check_servings(servings: Option<i32>) -> Result<(), String> {
+    match servings {
+        Some(servings) if !(1..=MAX_SERVINGS).contains(&servings) => Err(format!("Servings must be between 1 and {}", MAX_SERVINGS)),
+        _ => Ok(()),
+    }
+}
+
+/// The amount multiplied by `factor`, in the unit that reads best and rounded to what can be measured. Amounts
+/// without a quantity, such as "to taste", can't be scaled and give `None`
+pub fn scale_amount(amount: &Amount, factor: f64) -> Option<Amount> {
+    let quantity = amount.quantity? * factor;
+    let (quantity, unit) = match amount.unit {
+        Some(unit) => {
+            let (quantity, unit) = best_unit(quantity, unit);
+            (quantity, Some(unit))
+        },
+        None => (quantity, None),
+    };
+
+    let quantity = match unit {
+        Some(Unit::Gram | Unit::Millilitre) => round_small_metric(quantity),
+        Some(Unit::Kilogram | Unit::Litre) => round_to_step(quantity, 0.05),
+        _ => round_to_fraction(quantity),
+    };
+    Some(Amount { quantity: Some(quantity), unit, note: amount.note.clone() })
+}
+
+/// The quantity in the largest unit of the family it fills, or as it is for units with no family
+fn best_unit(quantity: f64, unit: Unit) -> (f64, Unit) {
+    let family = match UNIT_FAMILIES.iter().find(|family| family.iter().any(|(member, _, _)| *member == unit)) {
+        Some(family) => *family,
+        None => return (quantity, unit),
+    };
+
+    let size = family.iter().find(|(member, _, _)| *member == unit).map(|(_, size, _)| *size).unwrap_or(1.0);
+    let smallest_units = quantity * size;
+    family.iter()
+        .map(|(member, size, least)| (smallest_units / size, *member, *least))
+        .find(|(quantity, _, least)| quantity >= least)
+        .map(|(quantity, member, _)| (quantity, member))
+        .unwrap_or((quantity, unit))
+}
+
+/// Grams and millilitres to a tenth below 10, whole numbers below 100 and the nearest 5 above that
+fn round_small_metric(quantity: f64) -> f64 {
+    match quantity {
+        quantity if quantity < 10.0 => round_to_step(quantity, 0.1),
+        quantity if quantity < 100.0 => round_to_step(quantity, 1.0),
+        quantity => round_to_step(quantity, 5.0),
+    }
+}
+
+/// Nearest multiple of `step`, but never less than one step
+fn round_to_step(quantity: f64, step: f64) -> f64 {
+    ((quantity / step).round() * step).max(step)
+}
+
+/// Nearest whole number with a common fraction such as 1 1/2 or 2/3, or whole number for larger quantities. Never
+/// less than the smallest fraction
+fn round_to_fraction(quantity: f64) -> f64 {
+    if quantity >= WHOLE_NUMBERS_FROM {
+        return quantity.round();
+    }
+
+    let whole = quantity.trunc();
+    let fraction = quantity - whole;
+    let nearest = [0.0, 1.0].into_iter()
+        .chain(DISPLAY_FRACTIONS.iter().map(|(value, _)| *value))
+        .min_by(|a, b| (fraction - a).abs().total_cmp(&(fraction - b).abs()))
+        .unwrap_or(0.0);
+    (whole + nearest).max(DISPLAY_FRACTIONS[0].0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {} but got {}", expected, actual);
+    }
+
+    fn assert_unit(actual: (f64, Unit), expected: (f64, Unit)) {
+        assert_close(actual.0, expected.0);
+        assert_eq!(actual.1, expected.1);
+    }
+
+    fn scaled(text: &str, factor: f64) -> Option<String> {
+        scale_amount(&Amount::parse(text).unwrap(), factor).map(|amount| amount.to_string())
+    }
+
+    #[test]
+    fn promotes_spoons_to_cups() {
+        assert_unit(best_unit(12.0, Unit::Teaspoon), (0.25, Unit::Cup));
+        assert_unit(best_unit(3.0, Unit::Teaspoon), (1.0, Unit::Tablespoon));
+        assert_unit(best_unit(4.0, Unit::Tablespoon), (0.25, Unit::Cup));
+    }
+
+    #[test]
+    fn demotes_cups_to_spoons() {
+        assert_unit(best_unit(0.125, Unit::Cup), (2.0, Unit::Tablespoon));
+        assert_unit(best_unit(0.5, Unit::Tablespoon), (1.5, Unit::Teaspoon));
+        assert_unit(best_unit(2.0, Unit::Teaspoon), (2.0, Unit::Teaspoon));
+    }
+
+    #[test]
+    fn moves_between_grams_and_kilograms() {
+        assert_unit(best_unit(1200.0, Unit::Gram), (1.2, Unit::Kilogram));
+        assert_unit(best_unit(0.5, Unit::Kilogram), (500.0, Unit::Gram));
+        assert_unit(best_unit(999.0, Unit::Gram), (999.0, Unit::Gram));
+    }
+
+    #[test]
+    fn moves_between_millilitres_and_litres() {
+        assert_unit(best_unit(1500.0, Unit::Millilitre), (1.5, Unit::Litre));
+        assert_unit(best_unit(0.25, Unit::Litre), (250.0, Unit::Millilitre));
+    }
+
+    #[test]
+    fn moves_between_ounces_and_pounds() {
+        assert_unit(best_unit(24.0, Unit::Ounce), (1.5, Unit::Pound));
+        assert_unit(best_unit(0.5, Unit::Pound), (8.0, Unit::Ounce));
+    }
+
+    #[test]
+    fn leaves_units_without_a_family() {
+        assert_unit(best_unit(40.0, Unit::Clove), (40.0, Unit::Clove));
+        assert_unit(best_unit(0.1, Unit::Pinch), (0.1, Unit::Pinch));
+    }
+
+    #[test]
+    fn rounds_to_common_fractions() {
+        assert_close(round_to_fraction(1.3), 1.0 + 1.0 / 3.0);
+        assert_close(round_to_fraction(0.7), 2.0 / 3.0);
+        assert_close(round_to_fraction(2.1), 2.125);
+        assert_close(round_to_fraction(2.95), 3.0);
+    }
+
+    #[test]
+    fn rounds_large_quantities_to_whole_numbers() {
+        assert_close(round_to_fraction(9.9), 10.0);
+        assert_close(round_to_fraction(10.4), 10.0);
+        assert_close(round_to_fraction(12.5), 13.0);
+    }
+
+    #[test]
+    fn rounds_small_metric_by_size() {
+        assert_close(round_small_metric(2.34), 2.3);
+        assert_close(round_small_metric(10.4), 10.0);
+        assert_close(round_small_metric(99.4), 99.0);
+        assert_close(round_small_metric(100.0), 100.0);
+        assert_close(round_small_metric(123.0), 125.0);
+    }
+
+    #[test]
+    fn never_rounds_below_one_step() {
+        assert_close(round_to_fraction(0.01), 0.125);
+        assert_close(round_small_metric(0.01), 0.1);
+        assert_close(round_to_step(0.001, 0.05), 0.05);
+    }
+
+    #[test]
+    fn scales_amounts_into_the_best_unit() {
+        assert_eq!(scaled("12 tsp", 1.0).as_deref(), Some("1/4 cup"));
+        assert_eq!(scaled("1 tbsp", 0.5).as_deref(), Some("1 1/2 tsp"));
+        assert_eq!(scaled("600 g", 2.0).as_deref(), Some("1.2 kg"));
+        assert_eq!(scaled("8 oz", 3.0).as_deref(), Some("1 1/2 lb"));
+        assert_eq!(scaled("2 eggs", 1.5).as_deref(), Some("3 eggs"));
+    }
+
+    #[test]
+    fn leaves_amounts_without_a_quantity() {
+        assert_eq!(scaled("to taste", 2.0), None);
+        assert_eq!(scaled("1-2 cloves", 2.0), None);
+    }
+
+    #[test]
+    fn checks_servings_range() {
+        assert!(check_servings(None).is_ok());
+        assert!(check_servings(Some(1)).is_ok());
+        assert!(check_servings(Some(MAX_SERVINGS)).is_ok());
+        assert!(check_servings(Some(0)).is_err());
+        assert!(check_servings(Some(MAX_SERVINGS + 1)).is_err());
+    }
+}
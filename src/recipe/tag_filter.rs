@@ -0,0 +1,55 @@
+use anyhow::{anyhow, bail};
+use sqlx::{Postgres, QueryBuilder};
+use crate::http::QueryParams;
+use crate::recipe::ingredient_filter::MatchMode;
+
+/// `tag` filter on the recipe list.
+///
+/// Repeatable, naming tags ignoring case. `tag_mode` (default `all`) chooses whether recipes need every tag or any
+/// of them.
+#[derive(Debug)]
+pub struct TagFilter {
+    names: Vec<String>,
+    mode: MatchMode,
+}
+
+impl TagFilter {
+    pub fn from_params(params: &QueryParams) -> anyhow::Result<Self> {
+        let names = params.get_all("tag").into_iter().map(|name| name.trim().to_lowercase()).collect::<Vec<String>>();
+        if names.iter().any(String::is_empty) {
+            bail!("Tag filters need a tag name");
+        }
+
+        let mode = params.parse::<MatchMode>("tag_mode").map_err(|err| anyhow!(err))?.unwrap_or(MatchMode::All);
+        Ok(Self { names, mode })
+    }
+
+    /// Add the filter's conditions to a query over `recipe_overviews`, after its WHERE clause
+    pub fn push_conditions(&self, query: &mut QueryBuilder<Postgres>) {
+        if self.names.is_empty() {
+            return;
+        }
+
+        query.push(" AND ");
+        match self.mode {
+            MatchMode::All => {
+                query.push("(");
+                for (index, name) in self.names.iter().enumerate() {
+                    if index > 0 {
+                        query.push(" AND ");
+                    }
+                    push_has_tag(query, std::slice::from_ref(name));
+                }
+                query.push(")");
+            },
+            MatchMode::Any => push_has_tag(query, &self.names),
+        }
+    }
+}
+
+/// Whether the recipe has at least one of the tags named, given in lower case
+fn push_has_tag(query: &mut QueryBuilder<Postgres>, names: &[String]) {
+    query.push("EXISTS (SELECT 1 FROM recipe_tags
+        JOIN tags ON tags.id = recipe_tags.tag_id
+        WHERE recipe_tags.recipe_id = recipe_overviews.recipe_id AND lower(tags.name) = ANY(").push_bind(names.to_vec()).push("))");
+}
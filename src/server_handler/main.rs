@@ -1,21 +1,29 @@
+mod reload;
+
 use anyhow::{bail, Result};
 use backend::http::HttpCodec;
-use backend::{image, ingredient, recipe};
-use backend::{Config, ConfigFile};
+use backend::{audit, image, ingredient, recipe, tag, user};
+use backend::{Config, ConfigFile, RankingWeights, RateLimitConfig};
 use clap::Parser;
 use http::{Request, Response};
 use std::fs;
 use std::net::{TcpListener, TcpStream};
 use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use futures::executor::block_on;
 use sqlx::PgPool;
-use sqlx::postgres::PgPoolOptions;
 use backend::authorization::Authorization;
+use backend::http::ClientAddress;
+use backend::http::responses::too_many_requests_response;
+use backend::rate_limit::{Budget, RateLimiter};
 
 const DEFAULT_CONFIG: &str = "./config.toml";
+const MAX_DB_RETRY_DELAY: Duration = Duration::from_secs(30);
+const DEFAULT_SESSION_LIFETIME_HOURS: u64 = 30 * 24;
 
 /// Simple http server
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Address to bind to
@@ -24,7 +32,8 @@ struct Args {
 
     /// Config file
     #[arg(short, long, default_value = DEFAULT_CONFIG, long_help = "Path to a server config file. \
-    Config file options are overridden by arguments provided via the command line.")]
+    Config file options are overridden by ALACARTE_ prefixed environment variables (e.g. ALACARTE_DATABASE__ADDRESS), \
+    which are in turn overridden by arguments provided via the command line.")]
     config: Option<PathBuf>,
 
     /// Root image folder
@@ -34,25 +43,46 @@ struct Args {
     /// Log verbosity level
     #[arg(short, long)]
     verbosity: Option<log::Level>,
+
+    /// Parse free text ingredient amounts into quantities and units, then exit
+    #[arg(long)]
+    migrate_amounts: bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    let config = parse_args_into_config(args)?;
-    simple_logger::init_with_level(config.log_level)?;
+    let (config, warnings) = parse_args_into_config(args.clone())?;
+    // Log everything at the logger level so the verbosity can be raised on reload
+    simple_logger::init_with_level(log::Level::Trace)?;
+    log::set_max_level(config.log_level.to_level_filter());
+    for warning in warnings {
+        log::warn!("{}", warning);
+    }
 
-    let auth = Authorization::new(config.auth_file.clone());
+    let db_pool = block_on(create_db_connection(&config))?;
+    log::info!("Established database connection with {}", config.database.display_address());
+
+    if args.migrate_amounts {
+        let migrated = block_on(recipe::migrate_amounts(&db_pool))?;
+        log::info!("Migrated {} ingredient amounts", migrated);
+        return Ok(());
+    }
 
     let listener = TcpListener::bind(&config.address)?;
     log::info!("Bound TcpListener to {}", config.address);
-    let db_pool = block_on(create_db_connection(&config))?;
-    log::info!("Established database connection with {}", config.database.address);
+
+    let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    let auth = Arc::new(Authorization::new(&config, db_pool.clone(), rate_limiter.clone()));
+
+    let config = Arc::new(RwLock::new(config));
+    reload::reload_on_sighup(args, config.clone(), auth.clone(), rate_limiter.clone())?;
 
     for stream in listener.incoming() {
         log::info!("Incoming connection");
+        let config = config.read().expect("config lock poisoned").clone();
         match stream {
             Ok(stream) => {
-                match handle_connection(stream, &config, &db_pool, &auth) {
+                match handle_connection(stream, &config, &db_pool, &auth, &rate_limiter) {
                     Ok(_) => { log::info!("Successfully handled connection"); },
                     Err(err) => { log::error!("Error handling connection - {}", err); }
                 }
@@ -64,15 +94,26 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn handle_connection(stream: TcpStream, config: &Config, db_pool: &PgPool, auth: &Authorization) -> Result<()> {
+fn handle_connection(stream: TcpStream, config: &Config, db_pool: &PgPool, auth: &Authorization, rate_limiter: &RateLimiter) -> Result<()> {
+    let client = stream.peer_addr()?.ip();
     let mut http = HttpCodec::new(stream)?;
 
     let request = http.receive_request();
     log::info!("Received request");
     let response = match request {
-        Ok(request) => {
-            log::info!("Routing request '{}'", request.uri());
-            route_request(request, config, db_pool, auth)
+        Ok(mut request) => {
+            request.extensions_mut().insert(ClientAddress(client));
+            let budget = match *request.method() {
+                http::Method::GET | http::Method::HEAD => Budget::Read,
+                _ => Budget::Write,
+            };
+            match rate_limiter.try_acquire(budget, &client.to_string()) {
+                Ok(_) => {
+                    log::info!("Routing request '{}'", request.uri());
+                    route_request(request, config, db_pool, auth)
+                },
+                Err(retry_after) => too_many_requests_response(retry_after),
+            }
         },
         Err(err) => {
             log::error!("Error receiving request - {}", err);
@@ -89,12 +130,12 @@ fn handle_connection(stream: TcpStream, config: &Config, db_pool: &PgPool, auth:
 fn route_request(request: Request<Vec<u8>>, config: &Config, db_pool: &PgPool, auth: &Authorization) -> Response<Vec<u8>> {
     if image::can_handle_request(&request) {
         log::debug!("Routing request to image");
-        return image::handle_request(request, config, auth)
+        return image::handle_request(request, config, db_pool, auth)
     }
 
     if recipe::can_handle_request(&request) {
         log::debug!("Routing request to recipe");
-        return recipe::handle_request(request, db_pool, auth)
+        return recipe::handle_request(request, config, db_pool, auth)
     }
 
     if ingredient::can_handle_request(&request) {
@@ -102,20 +143,36 @@ fn route_request(request: Request<Vec<u8>>, config: &Config, db_pool: &PgPool, a
         return ingredient::handle_request(request, db_pool, auth)
     }
 
+    if user::can_handle_request(&request) {
+        log::debug!("Routing request to user");
+        return user::handle_request(request, db_pool, auth)
+    }
+
+    if tag::can_handle_request(&request) {
+        log::debug!("Routing request to tag");
+        return tag::handle_request(request, db_pool, auth)
+    }
+
+    if audit::can_handle_request(&request) {
+        log::debug!("Routing request to audit");
+        return audit::handle_request(request, db_pool, auth)
+    }
+
     log::info!("No valid route for request '{}'", request.uri());
     http::Response::builder().status(http::StatusCode::BAD_REQUEST).body(vec![]).unwrap()
 }
 
-fn parse_args_into_config(args: Args) -> Result<Config> {
+/// The config, and warnings about it to log
+pub(crate) fn parse_args_into_config(args: Args) -> Result<(Config, Vec<String>)> {
     let config_file = args.config.clone();
     let config_file = config_file.unwrap_or(PathBuf::from(DEFAULT_CONFIG));
 
-    let config = match config_file.exists() {
-        true => toml::from_str(&fs::read_to_string(config_file)?)?,
+    let (config, warnings) = match config_file.exists() {
+        true => ConfigFile::from_str_with_env(&fs::read_to_string(config_file)?)?,
         false => bail!("Config file does not exist: {}", config_file.display())
     };
 
-    config_from_file_and_args(config, args)
+    Ok((config_from_file_and_args(config, args)?, warnings))
 }
 
 fn config_from_file_and_args(config: ConfigFile, args: Args) -> Result<Config> {
@@ -146,17 +203,37 @@ fn config_from_file_and_args(config: ConfigFile, args: Args) -> Result<Config> {
 
     let auth_file = config.auth_file.unwrap_or_else(|| PathBuf::from(".auth"));
 
-    Ok(Config { address, image_folder, database, log_level, auth_file })
+    let session_lifetime_hours = config.session_lifetime_hours.unwrap_or(DEFAULT_SESSION_LIFETIME_HOURS);
+    let session_lifetime = Duration::from_secs(session_lifetime_hours * 60 * 60);
+
+    let rate_limit = match config.rate_limit {
+        Some(rate_limit) => RateLimitConfig::try_from(rate_limit)?,
+        None => RateLimitConfig::default(),
+    };
+
+    let ranking = match config.ranking {
+        Some(ranking) => RankingWeights::try_from(ranking)?,
+        None => RankingWeights::default(),
+    };
+
+    Ok(Config { address, image_folder, database, log_level, auth_file, session_lifetime, rate_limit, ranking })
 }
 
 async fn create_db_connection(config: &Config) -> anyhow::Result<PgPool> {
-    let username = &config.database.username;
-    let password = &config.database.password;
-    let address = &config.database.address;
-    let name = &config.database.name;
-    let conn = format!("postgresql://{username}:{password}@{address}/{name}");
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&conn).await?;
-    Ok(pool)
-}
\ No newline at end of file
+    let connect_options = config.database.connect_options()?;
+    let mut retry_delay = config.database.connect_retry_delay;
+    let mut attempt = 0;
+    loop {
+        match config.database.pool_options().connect_with(connect_options.clone()).await {
+            Ok(pool) => return Ok(pool),
+            Err(err) if attempt < config.database.connect_retries => {
+                attempt += 1;
+                log::warn!("Failed to connect to database ({}/{}), retrying in {:?} - {}",
+                    attempt, config.database.connect_retries, retry_delay, err);
+                std::thread::sleep(retry_delay);
+                retry_delay = (retry_delay * 2).min(MAX_DB_RETRY_DELAY);
+            },
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::sync::{Arc, RwLock};
+use std::thread;
+use anyhow::Result;
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+use backend::authorization::Authorization;
+use backend::Config;
+use backend::rate_limit::RateLimiter;
+use crate::{parse_args_into_config, Args};
+
+/// Spawn a thread that reloads the config whenever the process receives SIGHUP
+pub fn reload_on_sighup(args: Args, config: Arc<RwLock<Config>>, auth: Arc<Authorization>, rate_limiter: Arc<RateLimiter>) -> Result<()> {
+    let mut signals = Signals::new([SIGHUP])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            log::info!("Received SIGHUP, reloading config");
+            match reload_config(&args, &config, &auth, &rate_limiter) {
+                Ok(_) => log::info!("Reloaded config"),
+                Err(err) => log::error!("Failed to reload config, keeping current config - {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn reload_config(args: &Args, config: &RwLock<Config>, auth: &Authorization, rate_limiter: &RateLimiter) -> Result<()> {
+    let (reloaded, warnings) = parse_args_into_config(args.clone())?;
+    reloaded.validate()?;
+    for warning in warnings {
+        log::warn!("{}", warning);
+    }
+
+    let mut config = config.write().expect("config lock poisoned");
+    for setting in config.restart_required_changes(&reloaded) {
+        log::warn!("Config setting '{}' changed, but requires a restart to take effect", setting);
+    }
+
+    log::set_max_level(reloaded.log_level.to_level_filter());
+    auth.apply_config(&reloaded);
+    rate_limiter.set_config(reloaded.rate_limit.clone());
+    config.apply_reload(reloaded);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+mod get_tags;
+mod edit_tag;
+
+use futures::executor::block_on;
+use http::{Method, Request, Response};
+use sqlx::PgPool;
+use crate::authorization::Authorization;
+use crate::http::responses::{bad_request_response, method_not_allowed_response};
+use crate::recipe::chunk_url;
+
+pub fn can_handle_request(request: &Request<Vec<u8>>) -> bool {
+    request.uri().path().starts_with("/tag/") || request.uri().path() == "/tag"
+}
+
+pub fn handle_request(request: Request<Vec<u8>>, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
+    match *request.method() {
+        Method::GET => handle_get_request(&request, db_pool),
+        Method::POST | Method::PUT | Method::DELETE => handle_write_request(&request, db_pool, auth_handler),
+        _ => method_not_allowed_response()
+    }
+}
+
+fn handle_get_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
+    match chunk_url(request.uri()).as_slice() {
+        ["tag"] => block_on(get_tags::get_all_tags(db_pool)),
+        ["tag", tag_id] => block_on(get_tags::get_tag_with_id(db_pool, tag_id)),
+        _ => bad_request_response()
+    }
+}
+
+fn handle_write_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let url_chunks = chunk_url(request.uri());
+    let valid_path = match *request.method() {
+        Method::POST => matches!(url_chunks.as_slice(), ["tag"]),
+        _ => matches!(url_chunks.as_slice(), ["tag", _]),
+    };
+    if !valid_path {
+        return bad_request_response()
+    }
+
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    match (request.method(), url_chunks.as_slice()) {
+        (&Method::POST, _) => block_on(edit_tag::handle_post_tag_request(request, db_pool, &identity)),
+        (&Method::PUT, ["tag", tag_id]) => block_on(edit_tag::handle_put_tag_request(request, tag_id, db_pool, &identity)),
+        (&Method::DELETE, ["tag", tag_id]) => block_on(edit_tag::handle_delete_tag_request(tag_id, db_pool, &identity)),
+        _ => bad_request_response()
+    }
+}
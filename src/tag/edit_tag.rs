@@ -0,0 +1,167 @@
+use http::{Request, Response};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use crate::audit::{self, Action, AuditEntry};
+use crate::authorization::Identity;
+use crate::http::responses;
+use crate::recipe;
+
+const MAX_TAG_NAME_LENGTH: usize = 32;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TagRequestData {
+    pub name: String,
+}
+
+pub async fn handle_post_tag_request(request: &Request<Vec<u8>>, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let name = match parse_tag_name(request) {
+        Ok(name) => name,
+        Err(response) => return *response,
+    };
+
+    match create_tag(&name, db_pool, identity).await {
+        Ok(tag_id) => {
+            log::info!("Tag {} created by {}", tag_id, identity);
+            responses::created(format!("/tag/{}", tag_id))
+        },
+        Err(err) if is_unique_violation(&err) => {
+            responses::conflict_response_with_message(&format!("Tag '{}' already exists", name))
+        },
+        Err(err) => {
+            log::error!("Error creating tag '{}' - {}", name, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Renames the tag, which renames it on every recipe that has it and so gives those recipes a new version
+pub async fn handle_put_tag_request(request: &Request<Vec<u8>>, tag_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let tag_id = match tag_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("Tag id must be an integer")
+    };
+    let name = match parse_tag_name(request) {
+        Ok(name) => name,
+        Err(response) => return *response,
+    };
+
+    match rename_tag(tag_id, &name, db_pool, identity).await {
+        Ok(true) => {
+            log::info!("Tag {} renamed by {}", tag_id, identity);
+            responses::empty_ok()
+        },
+        Ok(false) => responses::not_found_response(),
+        Err(err) if is_unique_violation(&err) => {
+            responses::conflict_response_with_message(&format!("Tag '{}' already exists", name))
+        },
+        Err(err) => {
+            log::error!("Error renaming tag {} - {}", tag_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Deletes the tag and takes it off every recipe, giving those recipes a new version
+pub async fn handle_delete_tag_request(tag_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let tag_id = match tag_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("Tag id must be an integer")
+    };
+
+    match delete_tag(tag_id, db_pool, identity).await {
+        Ok(true) => {
+            log::info!("Tag {} deleted by {}", tag_id, identity);
+            responses::empty_ok()
+        },
+        Ok(false) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error deleting tag {} - {}", tag_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Returns the id of the new tag
+async fn create_tag(name: &str, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<i64> {
+    let mut tx = db_pool.begin().await?;
+    let tag_id: i64 = sqlx::query_scalar("INSERT INTO tags (name) VALUES ($1) RETURNING id;")
+        .bind(name)
+        .fetch_one(&mut *tx).await?;
+
+    record_tag_change(&mut *tx, identity, tag_id, Action::Create, None, Some(name)).await?;
+    tx.commit().await?;
+    Ok(tag_id)
+}
+
+/// Returns whether the tag exists
+async fn rename_tag(tag_id: i64, name: &str, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<bool> {
+    let mut tx = db_pool.begin().await?;
+    let recipe_ids = recipe::tagged_recipe_ids(tag_id, &mut tx).await?;
+    let previous_name: Option<String> = sqlx::query_scalar("UPDATE tags AS updated
+            SET name = $1
+            FROM tags AS previous
+            WHERE updated.id = $2 AND previous.id = updated.id
+            RETURNING previous.name;")
+        .bind(name)
+        .bind(tag_id)
+        .fetch_optional(&mut *tx).await?;
+    let previous_name = match previous_name {
+        Some(previous_name) => previous_name,
+        None => return Ok(false),
+    };
+
+    recipe::bump_recipe_versions(&recipe_ids, identity, &mut tx).await?;
+    record_tag_change(&mut *tx, identity, tag_id, Action::Update, Some(&previous_name), Some(name)).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Returns whether the tag existed
+async fn delete_tag(tag_id: i64, db_pool: &PgPool, identity: &Identity) -> anyhow::Result<bool> {
+    let mut tx = db_pool.begin().await?;
+    // Read before deleting, which takes the tag off its recipes
+    let recipe_ids = recipe::tagged_recipe_ids(tag_id, &mut tx).await?;
+    let name: Option<String> = sqlx::query_scalar("DELETE FROM tags WHERE id = $1 RETURNING name;")
+        .bind(tag_id)
+        .fetch_optional(&mut *tx).await?;
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+
+    recipe::bump_recipe_versions(&recipe_ids, identity, &mut tx).await?;
+    record_tag_change(&mut *tx, identity, tag_id, Action::Delete, Some(&name), None).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+fn is_unique_violation(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<sqlx::Error>(), Some(sqlx::Error::Database(err)) if err.is_unique_violation())
+}
+
+/// The trimmed tag name from the request body
+fn parse_tag_name(request: &Request<Vec<u8>>) -> Result<String, Box<Response<Vec<u8>>>> {
+    let tag_request: TagRequestData = serde_json::from_slice(request.body()).map_err(|err| {
+        log::info!("Failed to parse tag request - {}", err);
+        Box::new(responses::bad_request_response_with_message("Invalid request body"))
+    })?;
+
+    let name = tag_request.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_TAG_NAME_LENGTH {
+        return Err(Box::new(responses::bad_request_response_with_message(&format!("Tag name must be between 1 and {} characters", MAX_TAG_NAME_LENGTH))));
+    }
+    Ok(name)
+}
+
+async fn record_tag_change<'e>(executor: impl PgExecutor<'e>, identity: &Identity, tag_id: i64, action: Action, before: Option<&str>, after: Option<&str>) -> anyhow::Result<()> {
+    audit::record(executor, AuditEntry {
+        actor: identity,
+        resource: "tag",
+        resource_id: tag_id.to_string(),
+        action,
+        before: before.map(|name| json!({ "tag_id": tag_id, "name": name })),
+        after: after.map(|name| json!({ "tag_id": tag_id, "name": name })),
+    }).await
+}
@@ -0,0 +1,64 @@
+use http::Response;
+use serde::Serialize;
+use sqlx::PgPool;
+use crate::http::responses::{bad_request_response_with_message, internal_server_error_response, json_ok, not_found_response};
+
+/// A tag and how many recipes have it, for building navigation
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct TagItem {
+    pub tag_id: i64,
+    pub name: String,
+    pub recipe_count: i64,
+}
+
+const TAG_ITEM_QUERY: &str = "SELECT tags.id AS tag_id,
+        tags.name,
+        COUNT(recipes.id) AS recipe_count
+    FROM tags
+    LEFT JOIN recipe_tags ON recipe_tags.tag_id = tags.id
+    LEFT JOIN recipes ON recipes.id = recipe_tags.recipe_id AND recipes.deleted_at IS NULL";
+
+pub async fn get_all_tags(db_pool: &PgPool) -> Response<Vec<u8>> {
+    let tags: Vec<TagItem> = match sqlx::query_as(&format!("{} GROUP BY tags.id ORDER BY lower(tags.name);", TAG_ITEM_QUERY))
+        .fetch_all(db_pool).await {
+        Ok(tags) => tags,
+        Err(err) => {
+            log::error!("Error handling get all tags request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+    to_json_response(&tags)
+}
+
+pub async fn get_tag_with_id(db_pool: &PgPool, tag_id: &str) -> Response<Vec<u8>> {
+    let tag_id = match tag_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return bad_request_response_with_message("Tag id must be an integer")
+    };
+
+    match fetch_tag(db_pool, tag_id).await {
+        Ok(Some(tag)) => to_json_response(&tag),
+        Ok(None) => not_found_response(),
+        Err(err) => {
+            log::error!("Error handling get tag request: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
+
+pub async fn fetch_tag(db_pool: &PgPool, tag_id: i64) -> anyhow::Result<Option<TagItem>> {
+    let tag = sqlx::query_as(&format!("{} WHERE tags.id = $1 GROUP BY tags.id;", TAG_ITEM_QUERY))
+        .bind(tag_id)
+        .fetch_optional(db_pool).await?;
+    Ok(tag)
+}
+
+fn to_json_response<T: Serialize>(value: &T) -> Response<Vec<u8>> {
+    match serde_json::to_string(value) {
+        Ok(json) => json_ok(json),
+        Err(err) => {
+            log::error!("Error serializing tag response: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
@@ -0,0 +1,93 @@
+mod get_user;
+mod login;
+mod pantry;
+mod password;
+mod put_user;
+mod register;
+mod role;
+
+use futures::executor::block_on;
+use http::{Method, Request, Response};
+use sqlx::PgPool;
+use crate::authorization::Authorization;
+use crate::http::responses::{bad_request_response, method_not_allowed_response};
+use crate::recipe::chunk_url;
+
+pub fn can_handle_request(request: &Request<Vec<u8>>) -> bool {
+    request.uri().path().starts_with("/user/") || request.uri().path() == "/user"
+}
+
+pub fn handle_request(request: Request<Vec<u8>>, db_pool: &PgPool, auth_handler: &Authorization) -> Response<Vec<u8>> {
+    if matches!(chunk_url(request.uri()).as_slice(), ["user", _, "pantry", ..]) {
+        return handle_pantry_request(&request, db_pool, auth_handler);
+    }
+
+    match *request.method() {
+        Method::GET => handle_get_request(&request, db_pool),
+        Method::POST => handle_post_request(&request, db_pool, auth_handler),
+        Method::PUT => handle_put_request(&request, db_pool, auth_handler),
+        _ => method_not_allowed_response()
+    }
+}
+
+fn handle_get_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
+    let url_chunks = chunk_url(request.uri());
+
+    match url_chunks.as_slice() {
+        ["user", user_id] => block_on(get_user::get_user_with_id(db_pool, user_id)),
+        ["user", user_id, "recipe"] => block_on(get_user::get_user_recipes(db_pool, user_id)),
+        _ => bad_request_response()
+    }
+}
+
+fn handle_post_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let url_chunks = chunk_url(request.uri());
+
+    match url_chunks.as_slice() {
+        ["user"] => block_on(register::handle_register_request(request, db_pool)),
+        ["user", "login"] => block_on(login::handle_login_request(request, db_pool, authorization)),
+        ["user", "logout"] => block_on(login::handle_logout_request(request, authorization)),
+        _ => bad_request_response()
+    }
+}
+
+fn handle_put_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let url_chunks = chunk_url(request.uri());
+    if !matches!(url_chunks.as_slice(), ["user", _] | ["user", _, "role"]) {
+        return bad_request_response()
+    }
+
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    match url_chunks.as_slice() {
+        ["user", user_id] => block_on(put_user::handle_put_user_request(request, user_id, db_pool, &identity)),
+        ["user", user_id, "role"] => block_on(role::handle_put_role_request(request, user_id, db_pool, &identity)),
+        _ => bad_request_response()
+    }
+}
+
+fn handle_pantry_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    let identity = match authorization.authenticate_route(request) {
+        Ok(identity) => identity,
+        Err(err) => {
+            return err.response();
+        }
+    };
+
+    let url_chunks = chunk_url(request.uri());
+    match (request.method(), url_chunks.as_slice()) {
+        (&Method::GET, ["user", user_id, "pantry"]) => block_on(pantry::get_pantry(user_id, db_pool, &identity)),
+        (&Method::GET, ["user", user_id, "pantry", "use-soon"]) => block_on(pantry::get_use_soon(request, user_id, db_pool, &identity)),
+        (&Method::GET, ["user", user_id, "pantry", "match"]) => block_on(pantry::get_pantry_matches(request, user_id, db_pool, &identity)),
+        (&Method::POST, ["user", user_id, "pantry"]) => block_on(pantry::handle_post_pantry_request(request, user_id, db_pool, &identity)),
+        (&Method::PUT, ["user", user_id, "pantry", ingredient_id]) => block_on(pantry::handle_put_pantry_request(request, user_id, ingredient_id, db_pool, &identity)),
+        (&Method::DELETE, ["user", user_id, "pantry", ingredient_id]) => block_on(pantry::handle_delete_pantry_request(user_id, ingredient_id, db_pool, &identity)),
+        (&Method::GET | &Method::POST | &Method::PUT | &Method::DELETE, _) => bad_request_response(),
+        _ => method_not_allowed_response()
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use http::Response;
+use serde::Serialize;
+use sqlx::PgPool;
+use crate::http::responses::{bad_request_response, internal_server_error_response, json_ok, not_found_response};
+use crate::recipe::database::RecipeOverview;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct GetUserResponse {
+    pub user_id: i64,
+    pub user_name: String,
+    pub display_name: String,
+    pub bio: Option<String>,
+    pub avatar_uri: Option<String>,
+    pub recipe_count: i64,
+    pub joined_at: DateTime<Utc>,
+}
+
+pub async fn get_user_with_id(db_pool: &PgPool, id: &str) -> Response<Vec<u8>> {
+    let id = match id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return bad_request_response()
+    };
+
+    let user = match fetch_user(db_pool, id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return not_found_response(),
+        Err(err) => {
+            log::error!("Error handling get user request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    match serde_json::to_string(&user) {
+        Ok(json) => json_ok(json),
+        Err(err) => {
+            log::error!("Error handling get user request: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
+
+pub async fn get_user_recipes(db_pool: &PgPool, id: &str) -> Response<Vec<u8>> {
+    let id = match id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return bad_request_response()
+    };
+
+    match user_exists(db_pool, id).await {
+        Ok(true) => (),
+        Ok(false) => return not_found_response(),
+        Err(err) => {
+            log::error!("Error handling get user recipes request: {}", err);
+            return internal_server_error_response()
+        }
+    }
+
+    let recipes = RecipeOverview::get_recipe_overviews_for_user(id, db_pool).await;
+    let json = match recipes {
+        Ok(recipes) => serde_json::to_string(&recipes),
+        Err(err) => {
+            log::error!("Error handling get user recipes request: {}", err);
+            return internal_server_error_response()
+        }
+    };
+
+    match json {
+        Ok(json) => json_ok(json),
+        Err(err) => {
+            log::error!("Error handling get user recipes request: {}", err);
+            internal_server_error_response()
+        }
+    }
+}
+
+async fn fetch_user(db_pool: &PgPool, user_id: i64) -> anyhow::Result<Option<GetUserResponse>> {
+    let user = sqlx::query_as("SELECT
+                users.id AS user_id,
+                users.name AS user_name,
+                COALESCE(users.display_name, users.name) AS display_name,
+                users.bio,
+                users.avatar_uri,
+                (SELECT COUNT(*) FROM recipes WHERE recipes.user_id = users.id AND recipes.deleted_at IS NULL) AS recipe_count,
+                users.created_at AS joined_at
+            FROM users
+            WHERE users.id = $1;")
+        .bind(user_id)
+        .fetch_optional(db_pool).await?;
+    Ok(user)
+}
+
+async fn user_exists(db_pool: &PgPool, user_id: i64) -> anyhow::Result<bool> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1);")
+        .bind(user_id)
+        .fetch_one(db_pool).await?;
+    Ok(exists)
+}
@@ -0,0 +1,83 @@
+use http::{Request, Response};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::authorization::Authorization;
+use crate::http::responses;
+use crate::user::password::verify_password;
+
+#[derive(Debug, Deserialize)]
+struct LoginRequestData {
+    pub user_name: String,
+    pub password: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct UserCredentials {
+    id: i64,
+    password_hash: Option<String>,
+}
+
+pub async fn handle_login_request(request: &Request<Vec<u8>>, db_pool: &PgPool, authorization: &Authorization) -> Response<Vec<u8>> {
+    if let Err(err) = authorization.check_failed_auth_budget(request) {
+        return err.response();
+    }
+
+    let login_request: LoginRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse login request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    let credentials = match fetch_user_credentials(login_request.user_name.trim(), db_pool).await {
+        Ok(credentials) => credentials,
+        Err(err) => {
+            log::error!("Error fetching credentials for user '{}' - {}", login_request.user_name, err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    let user_id = match credentials {
+        Some(UserCredentials { id, password_hash: Some(password_hash) }) if verify_password(&login_request.password, &password_hash) => id,
+        _ => {
+            log::info!("Failed login for user '{}'", login_request.user_name);
+            authorization.record_failed_auth(request);
+            return responses::unauthorized_response();
+        }
+    };
+
+    let session = match authorization.start_session(user_id).await {
+        Ok(session) => session,
+        Err(err) => {
+            log::error!("Error starting session for user {} - {}", user_id, err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    log::info!("User '{}' ({}) logged in", login_request.user_name, user_id);
+    match serde_json::to_string(&session) {
+        Ok(json) => responses::json_ok(json),
+        Err(err) => {
+            log::error!("Error serializing session - {}", err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+pub async fn handle_logout_request(request: &Request<Vec<u8>>, authorization: &Authorization) -> Response<Vec<u8>> {
+    match authorization.end_session(request).await {
+        Ok(_) => {
+            log::info!("Session ended");
+            responses::empty_ok()
+        },
+        Err(err) => err.response(),
+    }
+}
+
+async fn fetch_user_credentials(user_name: &str, db_pool: &PgPool) -> anyhow::Result<Option<UserCredentials>> {
+    let credentials = sqlx::query_as("SELECT id, password_hash FROM users WHERE lower(name) = lower($1);")
+        .bind(user_name)
+        .fetch_optional(db_pool).await?;
+    Ok(credentials)
+}
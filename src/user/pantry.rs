@@ -0,0 +1,326 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use http::{Request, Response};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use sqlx::types::Json;
+use crate::authorization::Identity;
+use crate::http::QueryParams;
+use crate::http::responses;
+use crate::recipe::database::RecipeOverview;
+use crate::recipe::match_recipes;
+
+const DEFAULT_USE_SOON_DAYS: i32 = 7;
+const MAX_USE_SOON_DAYS: i32 = 365;
+/// Recipes listed for each item about to expire
+const RECIPES_PER_ITEM: i64 = 10;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct PantryItem {
+    pub ingredient_id: i64,
+    pub ingredient_name: String,
+    pub quantity: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+    pub added_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UseSoonItemRow {
+    pub ingredient_id: i64,
+    pub ingredient_name: String,
+    pub quantity: Option<String>,
+    pub expires_on: NaiveDate,
+    pub expired: bool,
+    pub recipes: Json<Vec<RecipeOverview>>,
+}
+
+/// An item expiring soon, with recipes that would use it up
+#[derive(Debug, Serialize)]
+struct UseSoonItem {
+    pub ingredient_id: i64,
+    pub ingredient_name: String,
+    pub quantity: Option<String>,
+    pub expires_on: NaiveDate,
+    pub expired: bool,
+    pub recipes: Vec<RecipeOverview>,
+}
+
+impl From<UseSoonItemRow> for UseSoonItem {
+    fn from(row: UseSoonItemRow) -> Self {
+        UseSoonItem {
+            ingredient_id: row.ingredient_id,
+            ingredient_name: row.ingredient_name,
+            quantity: row.quantity,
+            expires_on: row.expires_on,
+            expired: row.expired,
+            recipes: row.recipes.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PostPantryItemRequestData {
+    pub ingredient_id: i64,
+    pub quantity: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+}
+
+/// Replaces the item, so missing fields are cleared
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PutPantryItemRequestData {
+    pub quantity: Option<String>,
+    pub expires_on: Option<NaiveDate>,
+}
+
+enum PantryWriteOutcome {
+    Written,
+    UnknownUser,
+    UnknownIngredient,
+    AlreadyInPantry,
+}
+
+pub async fn get_pantry(user_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match pantry_owner(user_id, identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+
+    let items: Vec<PantryItem> = match sqlx::query_as("SELECT user_pantry_items.ingredient_id,
+                ingredients.name AS ingredient_name,
+                user_pantry_items.quantity,
+                user_pantry_items.expires_on,
+                user_pantry_items.added_at
+            FROM user_pantry_items
+            JOIN ingredients ON ingredients.id = user_pantry_items.ingredient_id
+            WHERE user_pantry_items.user_id = $1
+            ORDER BY ingredients.name;")
+        .bind(user_id)
+        .fetch_all(db_pool).await {
+        Ok(items) => items,
+        Err(err) => {
+            log::error!("Error reading pantry of user {} - {}", user_id, err);
+            return responses::internal_server_error_response()
+        }
+    };
+    to_json_response(&items)
+}
+
+/// Items expiring within `days` (default 7), or already expired, soonest first, each with recipes using it
+pub async fn get_use_soon(request: &Request<Vec<u8>>, user_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match pantry_owner(user_id, identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+    let days = match QueryParams::from_uri(request.uri()).parse::<i32>("days") {
+        Ok(days) => days.unwrap_or(DEFAULT_USE_SOON_DAYS).clamp(0, MAX_USE_SOON_DAYS),
+        Err(message) => return responses::bad_request_response_with_message(&message),
+    };
+
+    let items: Vec<UseSoonItemRow> = match sqlx::query_as("SELECT user_pantry_items.ingredient_id,
+                ingredients.name AS ingredient_name,
+                user_pantry_items.quantity,
+                user_pantry_items.expires_on,
+                user_pantry_items.expires_on < current_date AS expired,
+                COALESCE(recipes.recipes, '[]'::JSONB) AS recipes
+            FROM user_pantry_items
+            JOIN ingredients ON ingredients.id = user_pantry_items.ingredient_id
+            LEFT JOIN LATERAL (
+                SELECT jsonb_agg(to_jsonb(using_recipes) ORDER BY using_recipes.recipe_id DESC) AS recipes
+                FROM (
+                    SELECT recipe_overviews.*
+                    FROM recipe_overviews
+                    JOIN recipe_ingredients ON recipe_ingredients.recipe_id = recipe_overviews.recipe_id
+                    WHERE recipe_ingredients.ingredient_id = user_pantry_items.ingredient_id
+                    ORDER BY recipe_overviews.recipe_id DESC
+                    LIMIT $3
+                ) using_recipes
+            ) recipes ON TRUE
+            WHERE user_pantry_items.user_id = $1
+                AND user_pantry_items.expires_on <= current_date + $2
+            ORDER BY user_pantry_items.expires_on, ingredients.name;")
+        .bind(user_id)
+        .bind(days)
+        .bind(RECIPES_PER_ITEM)
+        .fetch_all(db_pool).await {
+        Ok(items) => items,
+        Err(err) => {
+            log::error!("Error reading items to use soon from pantry of user {} - {}", user_id, err);
+            return responses::internal_server_error_response()
+        }
+    };
+
+    let items = items.into_iter().map(UseSoonItem::from).collect::<Vec<UseSoonItem>>();
+    to_json_response(&items)
+}
+
+/// Recipes ranked by how much of them the pantry covers, see `match_recipes`
+pub async fn get_pantry_matches(request: &Request<Vec<u8>>, user_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match pantry_owner(user_id, identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+    let limit = match QueryParams::from_uri(request.uri()).parse::<i64>("limit") {
+        Ok(limit) => limit.unwrap_or(match_recipes::DEFAULT_LIMIT).clamp(1, match_recipes::MAX_LIMIT),
+        Err(message) => return responses::bad_request_response_with_message(&message),
+    };
+
+    let ingredient_ids: Vec<i64> = match sqlx::query_scalar("SELECT ingredient_id FROM user_pantry_items WHERE user_id = $1;")
+        .bind(user_id)
+        .fetch_all(db_pool).await {
+        Ok(ids) => ids,
+        Err(err) => {
+            log::error!("Error reading pantry of user {} - {}", user_id, err);
+            return responses::internal_server_error_response()
+        }
+    };
+    match_recipes::match_recipes_response(db_pool, &ingredient_ids, limit).await
+}
+
+pub async fn handle_post_pantry_request(request: &Request<Vec<u8>>, user_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match pantry_owner(user_id, identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+
+    let item: PostPantryItemRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse post pantry item request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    match write_item(user_id, item.ingredient_id, item.quantity.as_deref(), item.expires_on, false, db_pool).await {
+        Ok(PantryWriteOutcome::Written) => {
+            log::info!("{} added ingredient {} to the pantry of user {}", identity, item.ingredient_id, user_id);
+            responses::created(format!("/user/{}/pantry/{}", user_id, item.ingredient_id))
+        },
+        Ok(outcome) => write_outcome_response(outcome, item.ingredient_id),
+        Err(err) => {
+            log::error!("Error adding ingredient {} to the pantry of user {} - {}", item.ingredient_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Adds the item, or replaces it if it is already in the pantry
+pub async fn handle_put_pantry_request(request: &Request<Vec<u8>>, user_id: &str, ingredient_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match pantry_owner(user_id, identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+    let ingredient_id = match ingredient_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("Ingredient id must be an integer")
+    };
+
+    let item: PutPantryItemRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse put pantry item request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    match write_item(user_id, ingredient_id, item.quantity.as_deref(), item.expires_on, true, db_pool).await {
+        Ok(PantryWriteOutcome::Written) => {
+            log::info!("{} set ingredient {} in the pantry of user {}", identity, ingredient_id, user_id);
+            responses::empty_ok()
+        },
+        Ok(outcome) => write_outcome_response(outcome, ingredient_id),
+        Err(err) => {
+            log::error!("Error setting ingredient {} in the pantry of user {} - {}", ingredient_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+pub async fn handle_delete_pantry_request(user_id: &str, ingredient_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match pantry_owner(user_id, identity) {
+        Ok(user_id) => user_id,
+        Err(response) => return *response,
+    };
+    let ingredient_id = match ingredient_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("Ingredient id must be an integer")
+    };
+
+    match sqlx::query("DELETE FROM user_pantry_items WHERE user_id = $1 AND ingredient_id = $2;")
+        .bind(user_id)
+        .bind(ingredient_id)
+        .execute(db_pool).await {
+        Ok(result) if result.rows_affected() > 0 => {
+            log::info!("{} removed ingredient {} from the pantry of user {}", identity, ingredient_id, user_id);
+            responses::empty_ok()
+        },
+        Ok(_) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error removing ingredient {} from the pantry of user {} - {}", ingredient_id, user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// The pantry's user id, if `identity` may see and change it
+fn pantry_owner(user_id: &str, identity: &Identity) -> Result<i64, Box<Response<Vec<u8>>>> {
+    let user_id = user_id.parse::<i64>()
+        .map_err(|_| Box::new(responses::bad_request_response_with_message("User id must be an integer")))?;
+
+    if !identity.may_modify(user_id) {
+        log::info!("{} may not use the pantry of user {}", identity, user_id);
+        return Err(Box::new(responses::forbidden_response_with_message("Only the user or an admin may use their pantry")));
+    }
+    Ok(user_id)
+}
+
+/// Insert the item, replacing an existing one when `replace` is set
+async fn write_item(user_id: i64, ingredient_id: i64, quantity: Option<&str>, expires_on: Option<NaiveDate>, replace: bool, db_pool: &PgPool) -> anyhow::Result<PantryWriteOutcome> {
+    let ingredient_exists: Option<i64> = sqlx::query_scalar("SELECT id FROM ingredients WHERE id = $1;")
+        .bind(ingredient_id)
+        .fetch_optional(db_pool).await?;
+    if ingredient_exists.is_none() {
+        return Ok(PantryWriteOutcome::UnknownIngredient);
+    }
+
+    let on_conflict = match replace {
+        true => "ON CONFLICT (user_id, ingredient_id) DO UPDATE SET quantity = EXCLUDED.quantity, expires_on = EXCLUDED.expires_on",
+        false => "",
+    };
+    let result = sqlx::query(&format!("INSERT INTO user_pantry_items
+            (user_id, ingredient_id, quantity, expires_on)
+            VALUES ($1, $2, $3, $4)
+            {};", on_conflict))
+        .bind(user_id)
+        .bind(ingredient_id)
+        .bind(quantity)
+        .bind(expires_on)
+        .execute(db_pool).await;
+
+    match result {
+        Ok(_) => Ok(PantryWriteOutcome::Written),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Ok(PantryWriteOutcome::AlreadyInPantry),
+        // The ingredient was checked above, so the user is missing
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => Ok(PantryWriteOutcome::UnknownUser),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_outcome_response(outcome: PantryWriteOutcome, ingredient_id: i64) -> Response<Vec<u8>> {
+    match outcome {
+        PantryWriteOutcome::Written => responses::empty_ok(),
+        PantryWriteOutcome::UnknownUser => responses::not_found_response(),
+        PantryWriteOutcome::UnknownIngredient => responses::bad_request_response_with_message(&format!("Unknown ingredient id {}", ingredient_id)),
+        PantryWriteOutcome::AlreadyInPantry => responses::conflict_response_with_message(&format!("Ingredient {} is already in the pantry", ingredient_id)),
+    }
+}
+
+fn to_json_response<T: Serialize>(value: &T) -> Response<Vec<u8>> {
+    match serde_json::to_string(value) {
+        Ok(json) => responses::json_ok(json),
+        Err(err) => {
+            log::error!("Error serializing pantry response: {}", err);
+            responses::internal_server_error_response()
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use http::{Request, Response};
+use serde::{Deserialize, Deserializer};
+use sqlx::PgPool;
+use crate::authorization::Identity;
+use crate::http::responses;
+
+const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+const MAX_BIO_LENGTH: usize = 2000;
+
+/// Profile fields to change - missing fields are left unchanged, while a null bio or avatar clears it
+#[derive(Debug, Deserialize)]
+struct PutUserRequestData {
+    pub display_name: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub bio: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub avatar_uri: Option<Option<String>>,
+}
+
+/// Wraps a field that was given, including as null, in `Some` so it can be told apart from a missing field
+fn present<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> {
+    Option::<String>::deserialize(deserializer).map(Some)
+}
+
+pub async fn handle_put_user_request(request: &Request<Vec<u8>>, user_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match user_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("User id must be an integer")
+    };
+
+    if !identity.may_modify(user_id) {
+        log::info!("{} may not edit the profile of user {}", identity, user_id);
+        return responses::forbidden_response_with_message("Only the user or an admin may edit their profile");
+    }
+
+    let put_user_request: PutUserRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse put user request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    if put_user_request.display_name.as_ref().is_some_and(|name| name.trim().is_empty() || name.chars().count() > MAX_DISPLAY_NAME_LENGTH) {
+        return responses::bad_request_response_with_message(&format!("Display name must be between 1 and {} characters", MAX_DISPLAY_NAME_LENGTH));
+    }
+    if put_user_request.bio.as_ref().and_then(Option::as_ref).is_some_and(|bio| bio.chars().count() > MAX_BIO_LENGTH) {
+        return responses::bad_request_response_with_message(&format!("Bio must be at most {} characters", MAX_BIO_LENGTH));
+    }
+
+    match update_user_profile(user_id, &put_user_request, db_pool).await {
+        Ok(true) => {
+            log::info!("{} updated the profile of user {}", identity, user_id);
+            responses::empty_ok()
+        },
+        Ok(false) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error updating profile of user {} - {}", user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Returns whether the user exists
+async fn update_user_profile(user_id: i64, profile: &PutUserRequestData, db_pool: &PgPool) -> anyhow::Result<bool> {
+    let result = sqlx::query("UPDATE users
+            SET display_name = COALESCE($1, display_name),
+                bio = CASE WHEN $2 THEN $3 ELSE bio END,
+                avatar_uri = CASE WHEN $4 THEN $5 ELSE avatar_uri END
+            WHERE id = $6;")
+        .bind(profile.display_name.as_deref().map(str::trim))
+        .bind(profile.bio.is_some())
+        .bind(profile.bio.as_ref().and_then(Option::as_deref))
+        .bind(profile.avatar_uri.is_some())
+        .bind(profile.avatar_uri.as_ref().and_then(Option::as_deref))
+        .bind(user_id)
+        .execute(db_pool).await?;
+    Ok(result.rows_affected() > 0)
+}
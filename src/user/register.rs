@@ -0,0 +1,70 @@
+use http::{Request, Response};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::http::responses;
+use crate::user::password::{hash_password, MIN_PASSWORD_LENGTH};
+
+const MAX_USER_NAME_LENGTH: usize = 64;
+
+#[derive(Debug, Deserialize)]
+struct RegisterRequestData {
+    pub user_name: String,
+    pub password: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct InsertedUser {
+    id: i64
+}
+
+pub async fn handle_register_request(request: &Request<Vec<u8>>, db_pool: &PgPool) -> Response<Vec<u8>> {
+    let register_request: RegisterRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse register request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    let user_name = register_request.user_name.trim();
+    if user_name.is_empty() || user_name.chars().count() > MAX_USER_NAME_LENGTH {
+        return responses::bad_request_response_with_message(&format!("User name must be between 1 and {} characters", MAX_USER_NAME_LENGTH));
+    }
+    if register_request.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return responses::bad_request_response_with_message(&format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+
+    let password_hash = match hash_password(&register_request.password) {
+        Ok(hash) => hash,
+        Err(err) => {
+            log::error!("{}", err);
+            return responses::internal_server_error_response();
+        }
+    };
+
+    match insert_user(user_name, &password_hash, db_pool).await {
+        Ok(user_id) => {
+            log::info!("Registered user '{}' ({})", user_name, user_id);
+            responses::created(format!("/user/{}", user_id))
+        },
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            log::info!("User name '{}' is already taken", user_name);
+            responses::conflict_response_with_message("User name is already taken")
+        },
+        Err(err) => {
+            log::error!("Error registering user '{}' - {}", user_name, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+async fn insert_user(user_name: &str, password_hash: &str, db_pool: &PgPool) -> Result<i64, sqlx::Error> {
+    let inserted_user: InsertedUser = sqlx::query_as("INSERT INTO users
+            (name, password_hash)
+            VALUES ($1, $2)
+            RETURNING id;")
+        .bind(user_name)
+        .bind(password_hash)
+        .fetch_one(db_pool).await?;
+    Ok(inserted_user.id)
+}
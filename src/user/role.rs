@@ -0,0 +1,48 @@
+use http::{Request, Response};
+use serde::Deserialize;
+use sqlx::PgPool;
+use crate::authorization::{Identity, Role};
+use crate::http::responses;
+
+#[derive(Debug, Deserialize)]
+struct PutRoleRequestData {
+    pub role: Role,
+}
+
+pub async fn handle_put_role_request(request: &Request<Vec<u8>>, user_id: &str, db_pool: &PgPool, identity: &Identity) -> Response<Vec<u8>> {
+    let user_id = match user_id.parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => return responses::bad_request_response_with_message("User id must be an integer")
+    };
+
+    let put_role_request: PutRoleRequestData = match serde_json::from_slice(request.body()) {
+        Ok(data) => data,
+        Err(err) => {
+            log::info!("Failed to parse role request - {}", err);
+            return responses::bad_request_response_with_message("Invalid request body");
+        }
+    };
+
+    match update_user_role(user_id, put_role_request.role, db_pool).await {
+        Ok(true) => {
+            log::info!("{} assigned role {} to user {}", identity, put_role_request.role, user_id);
+            responses::empty_ok()
+        },
+        Ok(false) => responses::not_found_response(),
+        Err(err) => {
+            log::error!("Error assigning role to user {} - {}", user_id, err);
+            responses::internal_server_error_response()
+        }
+    }
+}
+
+/// Returns whether the user exists
+async fn update_user_role(user_id: i64, role: Role, db_pool: &PgPool) -> anyhow::Result<bool> {
+    let result = sqlx::query("UPDATE users
+            SET role = $1
+            WHERE id = $2;")
+        .bind(role.as_str())
+        .bind(user_id)
+        .execute(db_pool).await?;
+    Ok(result.rows_affected() > 0)
+}